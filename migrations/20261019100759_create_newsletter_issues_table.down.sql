@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS issue_delivery_queue;
+DROP TABLE IF EXISTS newsletter_issues;
@@ -0,0 +1,20 @@
+-- `markdown_content` is the source the editors wrote, when they used Markdown; the HTML and
+-- plain-text bodies are rendered from it at publish time and are what gets sent
+CREATE TABLE newsletter_issues(
+  newsletter_issue_id uuid NOT NULL,
+  PRIMARY KEY(newsletter_issue_id),
+  title TEXT NOT NULL,
+  markdown_content TEXT,
+  html_content TEXT NOT NULL,
+  text_content TEXT NOT NULL,
+  published_at timestamptz NOT NULL
+);
+
+-- One row per email still to be sent, deleted once delivered
+CREATE TABLE issue_delivery_queue(
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  PRIMARY KEY(newsletter_issue_id, subscriber_email),
+  n_retries INT NOT NULL DEFAULT 0,
+  execute_after timestamptz NOT NULL DEFAULT now()
+);
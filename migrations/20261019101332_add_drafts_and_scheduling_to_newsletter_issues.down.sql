@@ -0,0 +1,8 @@
+-- Drafts and scheduled issues cannot be represented without these columns
+DELETE FROM newsletter_issues WHERE status <> 'published';
+DROP INDEX IF EXISTS newsletter_issues_due_idx;
+ALTER TABLE newsletter_issues
+  DROP COLUMN status,
+  DROP COLUMN created_at,
+  DROP COLUMN scheduled_for,
+  ALTER COLUMN published_at SET NOT NULL;
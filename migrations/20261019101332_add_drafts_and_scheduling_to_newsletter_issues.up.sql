@@ -0,0 +1,14 @@
+-- Issues start as drafts and are only queued for delivery once published, either by hand or by
+-- the scheduler when `scheduled_for` is due
+ALTER TABLE newsletter_issues
+  ADD COLUMN status TEXT NOT NULL DEFAULT 'published'
+    CHECK (status IN ('draft', 'scheduled', 'published')),
+  ADD COLUMN created_at timestamptz NOT NULL DEFAULT now(),
+  ADD COLUMN scheduled_for timestamptz,
+  ALTER COLUMN published_at DROP NOT NULL;
+UPDATE newsletter_issues SET created_at = published_at;
+ALTER TABLE newsletter_issues
+  ALTER COLUMN status DROP DEFAULT,
+  ALTER COLUMN created_at DROP DEFAULT;
+CREATE INDEX newsletter_issues_due_idx ON newsletter_issues (scheduled_for)
+  WHERE status = 'scheduled';
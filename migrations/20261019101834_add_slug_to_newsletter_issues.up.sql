@@ -0,0 +1,6 @@
+-- Published issues are reachable at `/archive/{slug}`, derived from the title at publish time
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT UNIQUE;
+UPDATE newsletter_issues SET slug = newsletter_issue_id::text WHERE status = 'published';
+ALTER TABLE newsletter_issues
+  ADD CONSTRAINT newsletter_issues_published_slug_check
+    CHECK (status <> 'published' OR slug IS NOT NULL);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS tracking_events;
+ALTER TABLE newsletter_issues DROP COLUMN IF EXISTS tracking;
@@ -0,0 +1,15 @@
+-- Tracking is opt-in per issue, and can be disabled for the whole deployment with
+-- `tracking.enabled`
+ALTER TABLE newsletter_issues ADD COLUMN tracking BOOLEAN NOT NULL DEFAULT false;
+
+-- One row per pixel load or tracked link followed. `subscriber_id` is not a foreign key: the stats
+-- of an issue do not change when subscribers leave
+CREATE TABLE tracking_events(
+  id BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_id uuid NOT NULL,
+  kind TEXT NOT NULL CHECK (kind IN ('open', 'click')),
+  url TEXT,
+  occurred_at timestamptz NOT NULL
+);
+CREATE INDEX tracking_events_issue_idx ON tracking_events (newsletter_issue_id, kind);
@@ -0,0 +1,17 @@
+-- The outcome of each email of an issue, once the worker is done with it
+CREATE TABLE issue_deliveries(
+  newsletter_issue_id uuid NOT NULL REFERENCES newsletter_issues (newsletter_issue_id),
+  subscriber_email TEXT NOT NULL,
+  -- Matches `tracking_events.subscriber_id`; not a foreign key, subscribers may leave
+  subscriber_id uuid NOT NULL,
+  status TEXT NOT NULL CHECK (status IN ('delivered', 'failed')),
+  attempts INT NOT NULL,
+  -- The id the provider gave the email, used to match the events it reports later on
+  provider_message_id TEXT,
+  -- Why the last attempt of a failed email did not go through
+  error TEXT,
+  recorded_at timestamptz NOT NULL,
+  bounced_at timestamptz,
+  PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
+CREATE INDEX issue_deliveries_provider_message_id_idx ON issue_deliveries (provider_message_id);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS email_events;
+ALTER TABLE subscriptions DROP COLUMN IF EXISTS status, DROP COLUMN IF EXISTS status_changed_at;
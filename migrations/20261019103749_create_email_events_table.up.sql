@@ -0,0 +1,16 @@
+-- Subscribers the provider reported as hard bouncing or complaining are no longer sent anything
+ALTER TABLE subscriptions
+  ADD COLUMN status TEXT NOT NULL DEFAULT 'active'
+    CHECK (status IN ('active', 'bounced', 'complained')),
+  ADD COLUMN status_changed_at timestamptz;
+
+-- Bounce and complaint events posted by the provider to `/webhooks/email-events`. The provider's
+-- event id is the primary key: redelivered events are recognised and ignored
+CREATE TABLE email_events(
+  provider_event_id TEXT PRIMARY KEY,
+  kind TEXT NOT NULL CHECK (kind IN ('hard_bounce', 'soft_bounce', 'spam_complaint')),
+  email TEXT NOT NULL,
+  provider_message_id TEXT,
+  occurred_at timestamptz NOT NULL,
+  received_at timestamptz NOT NULL
+);
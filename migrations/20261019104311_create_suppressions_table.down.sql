@@ -0,0 +1,5 @@
+DELETE FROM issue_deliveries WHERE status = 'suppressed';
+ALTER TABLE issue_deliveries DROP CONSTRAINT issue_deliveries_status_check;
+ALTER TABLE issue_deliveries
+  ADD CONSTRAINT issue_deliveries_status_check CHECK (status IN ('delivered', 'failed'));
+DROP TABLE IF EXISTS suppressions;
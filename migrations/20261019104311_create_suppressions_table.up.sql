@@ -0,0 +1,16 @@
+-- Addresses and whole domains we never send anything to, whoever they belong to
+CREATE TABLE suppressions(
+  kind TEXT NOT NULL CHECK (kind IN ('address', 'domain')),
+  -- Lowercase; domains in their ASCII (punycode) form
+  value TEXT NOT NULL,
+  reason TEXT NOT NULL CHECK (reason IN ('hard_bounce', 'spam_complaint', 'erasure', 'manual')),
+  note TEXT,
+  created_at timestamptz NOT NULL,
+  PRIMARY KEY (kind, value)
+);
+
+-- Emails the suppression list kept from being sent
+ALTER TABLE issue_deliveries DROP CONSTRAINT issue_deliveries_status_check;
+ALTER TABLE issue_deliveries
+  ADD CONSTRAINT issue_deliveries_status_check
+  CHECK (status IN ('delivered', 'failed', 'suppressed'));
@@ -0,0 +1,3 @@
+ALTER TABLE newsletter_issues DROP COLUMN IF EXISTS list_id;
+DROP TABLE IF EXISTS list_subscriptions;
+DROP TABLE IF EXISTS lists;
@@ -0,0 +1,31 @@
+-- Each newsletter we run is a list. `sender_email` and `sender_name` override the sender set in
+-- `email_client.sender_email` for the issues sent to the list
+CREATE TABLE lists(
+  list_id TEXT PRIMARY KEY,
+  name TEXT NOT NULL,
+  sender_email TEXT,
+  sender_name TEXT,
+  created_at timestamptz NOT NULL
+);
+
+-- Everything sent so far went to what becomes the `default` list
+INSERT INTO lists (list_id, name, created_at) VALUES ('default', 'Newsletter', now());
+
+-- A subscriber may follow several lists, and leave one without leaving the others. Bounces and
+-- complaints concern the address, they stay in `subscriptions.status`
+CREATE TABLE list_subscriptions(
+  list_id TEXT NOT NULL REFERENCES lists (list_id),
+  subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+  status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'unsubscribed')),
+  subscribed_at timestamptz NOT NULL,
+  status_changed_at timestamptz,
+  PRIMARY KEY (list_id, subscriber_id)
+);
+CREATE INDEX list_subscriptions_subscriber_id_idx ON list_subscriptions (subscriber_id);
+
+INSERT INTO list_subscriptions (list_id, subscriber_id, subscribed_at)
+SELECT 'default', id, subscribed_at FROM subscriptions;
+
+ALTER TABLE newsletter_issues
+  ADD COLUMN list_id TEXT NOT NULL DEFAULT 'default' REFERENCES lists (list_id);
+ALTER TABLE newsletter_issues ALTER COLUMN list_id DROP DEFAULT;
@@ -0,0 +1,4 @@
+-- When the bucket will have refilled completely: from then on it behaves like a missing one and
+-- can be deleted. Existing buckets are treated as full.
+ALTER TABLE rate_limit_buckets ADD COLUMN full_at timestamptz NOT NULL DEFAULT now();
+CREATE INDEX rate_limit_buckets_full_at_idx ON rate_limit_buckets (full_at);
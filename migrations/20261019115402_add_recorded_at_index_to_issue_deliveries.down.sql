@@ -0,0 +1 @@
+DROP INDEX IF EXISTS issue_deliveries_log_idx;
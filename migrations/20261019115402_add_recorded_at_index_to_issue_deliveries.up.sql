@@ -0,0 +1,3 @@
+-- Serves the recipient log of the delivery reports, one page at a time
+CREATE INDEX issue_deliveries_log_idx
+  ON issue_deliveries (newsletter_issue_id, recorded_at, subscriber_email);
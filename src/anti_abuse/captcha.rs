@@ -0,0 +1,166 @@
+use std::time::Duration;
+
+use reqwest::Client;
+use secrecy::{ExposeSecret, Secret};
+
+use super::{AbuseCheck, CheckFuture, Rejection, Submission};
+use crate::configuration::CaptchaSettings;
+
+/// Verifies CAPTCHA responses against a provider's `siteverify` endpoint.
+///
+/// hCaptcha, reCAPTCHA and Turnstile share the same protocol: a form with `secret` and
+/// `response`, answered with `{"success": bool, ...}`.
+pub struct CaptchaVerifier {
+    verify_url: String,
+    secret: Secret<String>,
+    http_client: Client,
+}
+
+#[derive(serde::Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+impl CaptchaVerifier {
+    pub fn new(settings: &CaptchaSettings) -> Self {
+        Self {
+            verify_url: settings.verify_url.clone(),
+            secret: settings.secret.clone(),
+            http_client: Client::builder()
+                .timeout(Duration::from_secs(settings.timeout_seconds))
+                .build()
+                .expect("Failed to build the CAPTCHA HTTP client"),
+        }
+    }
+
+    #[tracing::instrument(name = "Verifying CAPTCHA response", skip(self, response))]
+    async fn verify(&self, response: &str) -> Result<(), Rejection> {
+        let result = self
+            .http_client
+            .post(&self.verify_url)
+            .form(&[
+                ("secret", self.secret.expose_secret().as_str()),
+                ("response", response),
+            ])
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+        let verification: VerifyResponse = match result {
+            Ok(r) => r.json().await,
+            Err(e) => Err(e),
+        }
+        .map_err(|e| {
+            tracing::error!(error.cause_chain = ?e, "Failed to verify the CAPTCHA response");
+            Rejection::CaptchaUnavailable
+        })?;
+
+        if verification.success {
+            Ok(())
+        } else {
+            Err(Rejection::CaptchaFailed)
+        }
+    }
+}
+
+impl AbuseCheck for CaptchaVerifier {
+    fn check<'a>(&'a self, submission: &'a Submission<'a>) -> CheckFuture<'a> {
+        Box::pin(async move {
+            match submission.captcha_response {
+                Some(response) if !response.is_empty() => self.verify(response).await,
+                _ => Err(Rejection::MissingCaptcha),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_ok;
+    use secrecy::Secret;
+    use wiremock::{
+        matchers::{body_string_contains, method},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::CaptchaVerifier;
+    use crate::{
+        anti_abuse::{AbuseCheck, Rejection, Submission},
+        configuration::CaptchaSettings,
+    };
+
+    fn verifier(mock_server: &MockServer) -> CaptchaVerifier {
+        CaptchaVerifier::new(&CaptchaSettings {
+            verify_url: format!("{}/siteverify", mock_server.uri()),
+            secret: Secret::new("captcha-secret".into()),
+            timeout_seconds: 1,
+        })
+    }
+
+    fn submission(captcha_response: &str) -> Submission<'_> {
+        Submission {
+            captcha_response: Some(captcha_response),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn a_successful_verification_is_accepted() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_string_contains("secret=captcha-secret"))
+            .and(body_string_contains("response=human"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"success": true})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        assert_ok!(verifier(&mock_server).check(&submission("human")).await);
+    }
+
+    #[tokio::test]
+    async fn a_failed_verification_is_rejected() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"success": false})),
+            )
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(
+            verifier(&mock_server).check(&submission("bot")).await,
+            Err(Rejection::CaptchaFailed)
+        );
+    }
+
+    #[tokio::test]
+    async fn a_missing_response_is_rejected_without_calling_the_provider() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(
+            verifier(&mock_server).check(&submission("")).await,
+            Err(Rejection::MissingCaptcha)
+        );
+    }
+
+    #[tokio::test]
+    async fn an_unavailable_provider_rejects_the_submission() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(
+            verifier(&mock_server).check(&submission("human")).await,
+            Err(Rejection::CaptchaUnavailable)
+        );
+    }
+}
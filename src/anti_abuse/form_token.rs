@@ -0,0 +1,133 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+use super::{AbuseCheck, CheckFuture, Rejection, Submission};
+use crate::configuration::FormTokenSettings;
+
+/// Issues and verifies `<issued at>.<signature>` tokens, used to reject forms submitted faster
+/// than a human could fill them in.
+#[derive(Clone)]
+pub struct FormTokenSigner {
+    secret: Secret<String>,
+    min_fill_seconds: i64,
+    max_age_seconds: i64,
+}
+
+impl FormTokenSigner {
+    pub fn new(settings: &FormTokenSettings) -> Self {
+        Self {
+            secret: settings.secret.clone(),
+            min_fill_seconds: settings.min_fill_seconds as i64,
+            max_age_seconds: settings.max_age_seconds as i64,
+        }
+    }
+
+    pub fn issue(&self) -> String {
+        self.issue_at(Utc::now().timestamp())
+    }
+
+    fn issue_at(&self, issued_at: i64) -> String {
+        let signature = self.mac(issued_at).finalize().into_bytes();
+        format!("{}.{}", issued_at, hex::encode(signature))
+    }
+
+    fn verify_at(&self, token: &str, now: i64) -> Result<(), Rejection> {
+        let (issued_at, signature) = token.split_once('.').ok_or(Rejection::InvalidFormToken)?;
+        let issued_at: i64 = issued_at.parse().map_err(|_| Rejection::InvalidFormToken)?;
+        let signature = hex::decode(signature).map_err(|_| Rejection::InvalidFormToken)?;
+        //NOTE: `verify_slice` compares in constant time
+        self.mac(issued_at)
+            .verify_slice(&signature)
+            .map_err(|_| Rejection::InvalidFormToken)?;
+
+        let elapsed = now - issued_at;
+        if elapsed < self.min_fill_seconds {
+            Err(Rejection::SubmittedTooFast)
+        } else if elapsed > self.max_age_seconds {
+            Err(Rejection::ExpiredFormToken)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn mac(&self, issued_at: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(issued_at.to_string().as_bytes());
+        mac
+    }
+}
+
+impl AbuseCheck for FormTokenSigner {
+    fn check<'a>(&'a self, submission: &'a Submission<'a>) -> CheckFuture<'a> {
+        let result = match submission.form_token {
+            Some(token) => self.verify_at(token, Utc::now().timestamp()),
+            None => Err(Rejection::MissingFormToken),
+        };
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claim::assert_ok;
+    use secrecy::Secret;
+
+    use super::FormTokenSigner;
+    use crate::{anti_abuse::Rejection, configuration::FormTokenSettings};
+
+    fn signer() -> FormTokenSigner {
+        FormTokenSigner::new(&FormTokenSettings {
+            enabled: true,
+            secret: Secret::new("secret".into()),
+            min_fill_seconds: 3,
+            max_age_seconds: 3600,
+        })
+    }
+
+    #[test]
+    fn a_token_submitted_after_the_minimum_fill_time_is_accepted() {
+        let token = signer().issue_at(1_000);
+        assert_ok!(signer().verify_at(&token, 1_003));
+    }
+
+    #[test]
+    fn a_token_submitted_too_fast_is_rejected() {
+        let token = signer().issue_at(1_000);
+        assert_eq!(
+            signer().verify_at(&token, 1_001),
+            Err(Rejection::SubmittedTooFast)
+        );
+    }
+
+    #[test]
+    fn an_old_token_is_rejected() {
+        let token = signer().issue_at(1_000);
+        assert_eq!(
+            signer().verify_at(&token, 1_000 + 3601),
+            Err(Rejection::ExpiredFormToken)
+        );
+    }
+
+    #[test]
+    fn a_token_with_a_forged_timestamp_is_rejected() {
+        let token = signer().issue_at(1_000);
+        let forged = token.replacen("1000", "900", 1);
+        assert_eq!(
+            signer().verify_at(&forged, 1_001),
+            Err(Rejection::InvalidFormToken)
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        for token in ["", "1000", "1000.", "abc.def", "1000.zz"] {
+            assert_eq!(
+                signer().verify_at(token, 1_003),
+                Err(Rejection::InvalidFormToken)
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+mod captcha;
+mod form_token;
+
+pub use captcha::CaptchaVerifier;
+pub use form_token::FormTokenSigner;
+
+use std::{fmt, future::Future, pin::Pin};
+
+use crate::configuration::AntiAbuseSettings;
+
+/// The anti-abuse related fields of a subscription form.
+#[derive(Debug, Default)]
+pub struct Submission<'a> {
+    /// Hidden field that humans never fill in.
+    pub honeypot: Option<&'a str>,
+    /// Signed timestamp handed out when the form was rendered.
+    pub form_token: Option<&'a str>,
+    pub captcha_response: Option<&'a str>,
+}
+
+/// Why a submission was considered abusive. Meant for our logs, not for the client.
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    HoneypotFilled,
+    MissingFormToken,
+    InvalidFormToken,
+    SubmittedTooFast,
+    ExpiredFormToken,
+    MissingCaptcha,
+    CaptchaFailed,
+    CaptchaUnavailable,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::HoneypotFilled => "the honeypot field was filled in",
+            Self::MissingFormToken => "the form token is missing",
+            Self::InvalidFormToken => "the form token is not valid",
+            Self::SubmittedTooFast => "the form was submitted too fast",
+            Self::ExpiredFormToken => "the form token has expired",
+            Self::MissingCaptcha => "the CAPTCHA response is missing",
+            Self::CaptchaFailed => "the CAPTCHA verification failed",
+            Self::CaptchaUnavailable => "the CAPTCHA verifier could not be reached",
+        };
+        f.write_str(reason)
+    }
+}
+
+pub type CheckFuture<'a> = Pin<Box<dyn Future<Output = Result<(), Rejection>> + Send + 'a>>;
+
+/// A single check run against every subscription before it reaches the database.
+pub trait AbuseCheck: Send + Sync {
+    fn check<'a>(&'a self, submission: &'a Submission<'a>) -> CheckFuture<'a>;
+}
+
+/// Rejects submissions that filled in the honeypot field.
+pub struct Honeypot;
+
+impl AbuseCheck for Honeypot {
+    fn check<'a>(&'a self, submission: &'a Submission<'a>) -> CheckFuture<'a> {
+        let filled = submission.honeypot.is_some_and(|v| !v.is_empty());
+        Box::pin(async move {
+            if filled {
+                Err(Rejection::HoneypotFilled)
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+/// The checks enabled in [`AntiAbuseSettings`], run in order until one rejects the submission.
+pub struct AntiAbuse {
+    checks: Vec<Box<dyn AbuseCheck>>,
+    form_token_signer: FormTokenSigner,
+}
+
+impl AntiAbuse {
+    pub fn new(settings: &AntiAbuseSettings) -> Self {
+        let form_token_signer = FormTokenSigner::new(&settings.form_token);
+        let mut checks: Vec<Box<dyn AbuseCheck>> = Vec::new();
+        if settings.honeypot {
+            checks.push(Box::new(Honeypot));
+        }
+        if settings.form_token.enabled {
+            checks.push(Box::new(form_token_signer.clone()));
+        }
+        //NOTE: The CAPTCHA goes last, it is the only check that costs a network call
+        if let Some(captcha) = &settings.captcha {
+            checks.push(Box::new(CaptchaVerifier::new(captcha)));
+        }
+        Self {
+            checks,
+            form_token_signer,
+        }
+    }
+
+    pub async fn check(&self, submission: &Submission<'_>) -> Result<(), Rejection> {
+        for check in &self.checks {
+            check.check(submission).await?;
+        }
+        Ok(())
+    }
+
+    /// Issues a token to be embedded in the subscription form.
+    pub fn form_token(&self) -> String {
+        self.form_token_signer.issue()
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use minijinja::Environment;
+use serde::Serialize;
+
+use crate::{
+    configuration::ArchiveSettings, email_templates::load_environment, newsletters::ArchivedIssue,
+};
+
+/// The pages of the public archive (`index.html`, `issue.html`) and its Atom feed (`feed.xml`),
+/// loaded from `archive.directory`.
+///
+/// Every template gets `archive` (`title`, `path`, `url`, `feed_path`, `feed_url`); issues come
+/// with `title`, `path`, `url`, `published_at` (RFC 3339), `published_on` (human readable) and
+/// `html_content`. Pages link with paths, the feed with absolute URLs built from
+/// `application.base_url`.
+#[derive(Debug)]
+pub struct ArchivePages {
+    environment: Environment<'static>,
+    title: String,
+    base_url: String,
+}
+
+#[derive(Serialize)]
+struct Archive<'a> {
+    title: &'a str,
+    path: &'static str,
+    url: String,
+    feed_path: &'static str,
+    feed_url: String,
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    title: &'a str,
+    path: String,
+    url: String,
+    published_at: String,
+    published_on: String,
+    html_content: &'a str,
+}
+
+const ARCHIVE_PATH: &str = "/archive";
+const FEED_PATH: &str = "/archive/feed.xml";
+
+impl ArchivePages {
+    /// Loads the templates and renders each of them once against a sample issue, so that mistakes
+    /// stop the application at startup.
+    pub fn load(settings: &ArchiveSettings, base_url: &str) -> Result<Self, String> {
+        let pages = Self {
+            environment: load_environment(&settings.directory, &["html", "xml"])?,
+            title: settings.title.clone(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        };
+        let sample = ArchivedIssue {
+            slug: "sample-issue".into(),
+            title: "Sample issue".into(),
+            html_content: "<p>Sample content</p>".into(),
+            published_at: Utc::now(),
+        };
+        let invalid = |template, e| {
+            format!(
+                "Archive template `{}` cannot be rendered: {:#}",
+                template, e
+            )
+        };
+        pages
+            .index(std::slice::from_ref(&sample))
+            .map_err(|e| invalid("index.html", e))?;
+        pages.index(&[]).map_err(|e| invalid("index.html", e))?;
+        pages.issue(&sample).map_err(|e| invalid("issue.html", e))?;
+        pages
+            .feed(std::slice::from_ref(&sample))
+            .map_err(|e| invalid("feed.xml", e))?;
+        Ok(pages)
+    }
+
+    pub fn index(&self, issues: &[ArchivedIssue]) -> Result<String, minijinja::Error> {
+        let issues: Vec<_> = issues.iter().map(|issue| self.entry(issue)).collect();
+        self.environment
+            .get_template("index.html")?
+            .render(minijinja::context! { archive => self.archive(), issues })
+    }
+
+    pub fn issue(&self, issue: &ArchivedIssue) -> Result<String, minijinja::Error> {
+        self.environment
+            .get_template("issue.html")?
+            .render(minijinja::context! { archive => self.archive(), issue => self.entry(issue) })
+    }
+
+    /// `issues` are expected most recent first: the feed is as recent as its first entry.
+    pub fn feed(&self, issues: &[ArchivedIssue]) -> Result<String, minijinja::Error> {
+        let updated = issues
+            .first()
+            .map(|issue| issue.published_at)
+            .unwrap_or(DateTime::UNIX_EPOCH);
+        let issues: Vec<_> = issues.iter().map(|issue| self.entry(issue)).collect();
+        self.environment
+            .get_template("feed.xml")?
+            .render(minijinja::context! {
+                archive => self.archive(),
+                updated => updated.to_rfc3339(),
+                issues,
+            })
+    }
+
+    fn archive(&self) -> Archive<'_> {
+        Archive {
+            title: &self.title,
+            path: ARCHIVE_PATH,
+            url: format!("{}{}", self.base_url, ARCHIVE_PATH),
+            feed_path: FEED_PATH,
+            feed_url: format!("{}{}", self.base_url, FEED_PATH),
+        }
+    }
+
+    fn entry<'a>(&self, issue: &'a ArchivedIssue) -> Entry<'a> {
+        let path = format!("{}/{}", ARCHIVE_PATH, issue.slug);
+        Entry {
+            title: &issue.title,
+            url: format!("{}{}", self.base_url, path),
+            path,
+            published_at: issue.published_at.to_rfc3339(),
+            published_on: issue.published_at.format("%B %-d, %Y").to_string(),
+            html_content: &issue.html_content,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::ArchivePages;
+    use crate::{configuration::ArchiveSettings, newsletters::ArchivedIssue};
+
+    fn pages() -> ArchivePages {
+        let settings = ArchiveSettings {
+            enabled: true,
+            title: "Our newsletter".into(),
+            directory: concat!(env!("CARGO_MANIFEST_DIR"), "/templates/archive").into(),
+            feed_entries: 20,
+        };
+        ArchivePages::load(&settings, "https://newsletter.example.com/").unwrap()
+    }
+
+    fn issue() -> ArchivedIssue {
+        ArchivedIssue {
+            slug: "issue-1".into(),
+            title: "Issue #1 & more".into(),
+            html_content: "<p>Hello</p>".into(),
+            published_at: Utc.with_ymd_and_hms(2026, 10, 19, 8, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn issue_pages_show_the_content_as_is() {
+        let page = pages().issue(&issue()).unwrap();
+
+        assert!(page.contains("<h1>Issue #1 &amp; more</h1>"));
+        assert!(page.contains("<p>Hello</p>"));
+        assert!(page.contains("October 19, 2026"));
+    }
+
+    #[test]
+    fn the_feed_escapes_the_content_and_uses_absolute_urls() {
+        let feed = pages().feed(&[issue()]).unwrap();
+
+        assert!(feed.contains("&lt;p&gt;Hello&lt;&#x2f;p&gt;"));
+        assert!(feed.contains("https:&#x2f;&#x2f;newsletter.example.com&#x2f;archive&#x2f;issue-1"));
+        assert!(feed.contains("<updated>2026-10-19T08:00:00+00:00</updated>"));
+    }
+}
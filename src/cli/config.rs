@@ -0,0 +1,29 @@
+use clap::Subcommand;
+
+use crate::{
+    archive::ArchivePages, configuration::get_configuration, email_templates::EmailTemplates,
+};
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Load and validate the configuration for `APP_ENVIRONMENT`, reporting every problem found,
+    /// then check that the email and archive templates render.
+    Check,
+}
+
+pub fn config(command: ConfigCommand) -> std::io::Result<()> {
+    match command {
+        ConfigCommand::Check => {
+            let configuration = get_configuration()
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            EmailTemplates::load(&configuration.email_templates.directory)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            if configuration.archive.enabled {
+                ArchivePages::load(&configuration.archive, &configuration.application.base_url)
+                    .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+            }
+            println!("The configuration is valid");
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use clap::Subcommand;
+
+use crate::{configuration::Settings, migrations, startup::get_connection_pool};
+
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply every pending migration.
+    Up,
+    /// List migrations and whether they have been applied.
+    Status,
+    /// Revert the most recently applied migration.
+    Revert,
+}
+
+pub async fn migrate(command: MigrateCommand, configuration: &Settings) -> std::io::Result<()> {
+    let pool = get_connection_pool(&configuration.database);
+    match command {
+        MigrateCommand::Up => {
+            migrations::run(&pool)
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("The database is up to date");
+        }
+        MigrateCommand::Status => {
+            for migration in migrations::status(&pool)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                println!(
+                    "{:<16} {:<18} {}",
+                    migration.version,
+                    migration.state.as_str(),
+                    migration.description
+                );
+            }
+        }
+        MigrateCommand::Revert => {
+            match migrations::revert(&pool)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                Some(migration) => {
+                    println!("Reverted {} {}", migration.version, migration.description)
+                }
+                None => println!("There is no applied migration to revert"),
+            }
+        }
+    }
+    pool.close().await;
+    Ok(())
+}
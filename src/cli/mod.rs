@@ -0,0 +1,47 @@
+mod config;
+mod migrate;
+mod newsletters;
+mod queue;
+mod send_test_email;
+mod subscribers;
+
+pub use config::*;
+pub use migrate::*;
+pub use newsletters::*;
+pub use queue::*;
+pub use send_test_email::*;
+pub use subscribers::*;
+
+use clap::{Parser, Subcommand};
+
+/// Newsletter delivery service.
+#[derive(Debug, Parser)]
+#[command(name = "zero2prod", version)]
+pub struct Cli {
+    //NOTE: Starting the binary without arguments keeps serving requests, as the Docker image does
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Serve HTTP requests (the default).
+    Serve,
+    /// Manage the database schema.
+    #[command(subcommand)]
+    Migrate(MigrateCommand),
+    /// Inspect and manage subscribers.
+    #[command(subcommand)]
+    Subscribers(SubscribersCommand),
+    /// Publish newsletter issues.
+    #[command(subcommand)]
+    Newsletters(NewslettersCommand),
+    /// Inspect and retry the emails waiting to be sent.
+    #[command(subcommand)]
+    Queue(QueueCommand),
+    /// Send an email through the configured provider to check that delivery works.
+    SendTestEmail(SendTestEmailArgs),
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
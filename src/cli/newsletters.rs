@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+
+use clap::{Args, Subcommand};
+
+use crate::{
+    configuration::Settings,
+    lists::DEFAULT_LIST_ID,
+    newsletters::{publish_new_issue, IssueContent},
+    startup::get_connection_pool,
+};
+
+#[derive(Debug, Subcommand)]
+pub enum NewslettersCommand {
+    /// Publish an issue to every subscriber of a list. Emails are sent by the running application.
+    Publish(PublishArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PublishArgs {
+    #[arg(long)]
+    pub title: String,
+    /// Markdown file holding the body of the issue.
+    #[arg(long, conflicts_with = "html", required_unless_present = "html")]
+    pub markdown: Option<PathBuf>,
+    /// HTML file holding the body of the issue, as an alternative to `--markdown`.
+    #[arg(long)]
+    pub html: Option<PathBuf>,
+    /// Plain-text version of `--html`, generated from it when missing.
+    #[arg(long, requires = "html")]
+    pub text: Option<PathBuf>,
+    /// Track opens and clicks, unless tracking is disabled in the configuration.
+    #[arg(long)]
+    pub track: bool,
+    /// The id of the list to send the issue to.
+    #[arg(long, default_value = DEFAULT_LIST_ID)]
+    pub list: String,
+}
+
+pub async fn newsletters(
+    command: NewslettersCommand,
+    configuration: &Settings,
+) -> std::io::Result<()> {
+    match command {
+        NewslettersCommand::Publish(args) => {
+            let content = match (args.markdown, args.html) {
+                (Some(markdown), _) => {
+                    IssueContent::from_markdown(std::fs::read_to_string(markdown)?)
+                }
+                (None, Some(html)) => IssueContent::from_html(
+                    std::fs::read_to_string(html)?,
+                    args.text.map(std::fs::read_to_string).transpose()?,
+                ),
+                (None, None) => unreachable!("clap requires either `--markdown` or `--html`"),
+            };
+            let pool = get_connection_pool(&configuration.database);
+            let newsletter_issue_id =
+                publish_new_issue(&pool, &args.list, &args.title, &content, args.track)
+                    .await
+                    .map_err(std::io::Error::other)?;
+            pool.close().await;
+            println!("Published issue {}", newsletter_issue_id);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{configuration::Settings, startup::get_connection_pool};
+
+#[derive(Debug, Subcommand)]
+pub enum QueueCommand {
+    /// Show, for every issue with emails still to send, how many are pending, how many of those
+    /// already failed at least once, and when the next one is due.
+    Status,
+    /// Make the pending emails due right away, with a fresh retry count, e.g. once the email
+    /// provider is back up.
+    Retry {
+        /// Only retry the emails of this issue.
+        #[arg(long)]
+        issue: Option<Uuid>,
+    },
+}
+
+pub async fn queue(command: QueueCommand, configuration: &Settings) -> std::io::Result<()> {
+    let pool = get_connection_pool(&configuration.database);
+    match command {
+        QueueCommand::Status => {
+            let issues = queue_status(&pool).await.map_err(std::io::Error::other)?;
+            if issues.is_empty() {
+                println!("The queue is empty");
+            }
+            for issue in issues {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    issue.newsletter_issue_id,
+                    issue.title,
+                    issue.pending,
+                    issue.retrying,
+                    issue.next_attempt_at.to_rfc3339()
+                );
+            }
+        }
+        QueueCommand::Retry { issue } => {
+            let rescheduled = retry_now(&pool, issue)
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("Rescheduled {} emails", rescheduled);
+        }
+    }
+    pool.close().await;
+    Ok(())
+}
+
+struct QueuedIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    pending: i64,
+    /// Pending emails that failed at least once.
+    retrying: i64,
+    next_attempt_at: DateTime<Utc>,
+}
+
+/// Issues with emails still queued, the next one due first.
+async fn queue_status(pool: &PgPool) -> Result<Vec<QueuedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        QueuedIssue,
+        r#"
+    SELECT
+        q.newsletter_issue_id,
+        i.title,
+        count(*) AS "pending!",
+        count(*) FILTER (WHERE q.n_retries > 0) AS "retrying!",
+        min(q.execute_after) AS "next_attempt_at!"
+    FROM issue_delivery_queue q
+    JOIN newsletter_issues i ON i.newsletter_issue_id = q.newsletter_issue_id
+    GROUP BY q.newsletter_issue_id, i.title
+    ORDER BY min(q.execute_after)
+    "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns how many emails were rescheduled.
+#[tracing::instrument(name = "Retrying queued emails", skip(pool))]
+async fn retry_now(pool: &PgPool, newsletter_issue_id: Option<Uuid>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE issue_delivery_queue
+    SET execute_after = now(), n_retries = 0
+    WHERE $1::uuid IS NULL OR newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,33 @@
+use clap::Args;
+
+use crate::{configuration::Settings, domain::SubscriberEmail, startup::get_connection_pool};
+
+#[derive(Debug, Args)]
+pub struct SendTestEmailArgs {
+    /// Recipient of the test email.
+    #[arg(long)]
+    pub to: String,
+    #[arg(long, default_value = "Test email from zero2prod")]
+    pub subject: String,
+}
+
+pub async fn send_test_email(
+    args: SendTestEmailArgs,
+    configuration: &Settings,
+) -> std::io::Result<()> {
+    let recipient = SubscriberEmail::parse(args.to)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let email_client = configuration
+        .email_client
+        .client()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?
+        .with_suppression_list(get_connection_pool(&configuration.database));
+
+    let text = "If you can read this, zero2prod can deliver emails.";
+    email_client
+        .send_email(&recipient, &args.subject, &format!("<p>{}</p>", text), text)
+        .await
+        .map_err(std::io::Error::other)?;
+    println!("Sent a test email to {}", recipient.as_ref());
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use chrono::{DateTime, Utc};
+use clap::Subcommand;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::Settings,
+    domain::{EmailPolicy, NewSubscriber, SubscriberEmail, SubscriberName},
+    lists::{leave_list, list_exists, DEFAULT_LIST_ID},
+    routes::insert_subscriber,
+    startup::get_connection_pool,
+    suppressions::{suppress, SuppressionEntry, SuppressionReason},
+};
+
+#[derive(Debug, Subcommand)]
+pub enum SubscribersCommand {
+    /// List subscribers, oldest first, with their status (`active`, `bounced` or `complained`)
+    /// and the lists they are subscribed to.
+    List,
+    /// Add a subscriber, applying the same validation as the public form. An existing subscriber
+    /// is only added to the list.
+    Add {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        name: String,
+        /// The id of the list to subscribe to.
+        #[arg(long, default_value = DEFAULT_LIST_ID)]
+        list: String,
+    },
+    /// Remove a subscriber, or only unsubscribe them from a list.
+    Remove {
+        email: String,
+        /// The id of the list to unsubscribe from, keeping the subscriber on their other lists.
+        #[arg(long)]
+        list: Option<String>,
+    },
+    /// Erase everything known about an address (subscription, pending emails, delivery log,
+    /// opens and clicks, provider events) and suppress it, so that it is never mailed again.
+    Erase { email: String },
+}
+
+pub async fn subscribers(
+    command: SubscribersCommand,
+    configuration: &Settings,
+) -> std::io::Result<()> {
+    let pool = get_connection_pool(&configuration.database);
+    match command {
+        SubscribersCommand::List => {
+            for subscriber in list_subscribers(&pool)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    subscriber.subscribed_at.to_rfc3339(),
+                    subscriber.email,
+                    subscriber.name,
+                    subscriber.status,
+                    subscriber.lists
+                );
+            }
+        }
+        SubscribersCommand::Add { email, name, list } => {
+            let new_subscriber = NewSubscriber {
+                email: SubscriberEmail::parse(email).map_err(invalid_input)?,
+                name: SubscriberName::parse(name, &configuration.subscriber_name)
+                    .map_err(invalid_input)?,
+            };
+            EmailPolicy::new(&configuration.email_policy)?
+                .check(&new_subscriber.email)
+                .await
+                .map_err(invalid_input)?;
+            if !list_exists(&pool, &list)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("There is no list with id {}", list),
+                ));
+            }
+            insert_subscriber(&pool, &new_subscriber, &list)
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("Added {} to {}", new_subscriber.email.as_ref(), list);
+        }
+        SubscribersCommand::Remove {
+            email,
+            list: Some(list),
+        } => {
+            let email = SubscriberEmail::parse(email).map_err(invalid_input)?;
+            if leave_list(&pool, &email, &list)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                println!("Unsubscribed {} from {}", email, list);
+            } else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} is not subscribed to {}", email, list),
+                ));
+            }
+        }
+        SubscribersCommand::Remove { email, list: None } => {
+            //NOTE: Parsing normalizes the domain the same way it was when the subscriber was added
+            let email = SubscriberEmail::parse(email).map_err(invalid_input)?;
+            if remove_subscriber(&pool, &email)
+                .await
+                .map_err(std::io::Error::other)?
+            {
+                println!("Removed {}", email);
+            } else {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("There is no subscriber with email {}", email),
+                ));
+            }
+        }
+        SubscribersCommand::Erase { email } => {
+            let email = SubscriberEmail::parse(email).map_err(invalid_input)?;
+            erase_subscriber(&pool, &email)
+                .await
+                .map_err(std::io::Error::other)?;
+            println!("Erased and suppressed {}", email);
+        }
+    }
+    pool.close().await;
+    Ok(())
+}
+
+fn invalid_input(e: impl std::error::Error + Send + Sync + 'static) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+}
+
+struct SubscriberRow {
+    email: SubscriberEmail,
+    name: SubscriberName,
+    subscribed_at: DateTime<Utc>,
+    status: String,
+    /// Comma-separated ids of the lists the subscriber is active on.
+    lists: String,
+}
+
+async fn list_subscribers(pool: &PgPool) -> Result<Vec<SubscriberRow>, sqlx::Error> {
+    sqlx::query_as!(
+        SubscriberRow,
+        r#"
+    SELECT
+        s.email AS "email: SubscriberEmail",
+        s.name AS "name: SubscriberName",
+        s.subscribed_at,
+        s.status,
+        coalesce(
+            string_agg(l.list_id, ',' ORDER BY l.list_id) FILTER (WHERE l.status = 'active'),
+            ''
+        ) AS "lists!"
+    FROM subscriptions s
+    LEFT JOIN list_subscriptions l ON l.subscriber_id = s.id
+    GROUP BY s.id
+    ORDER BY s.subscribed_at
+    "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+async fn remove_subscriber(pool: &PgPool, email: &SubscriberEmail) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM subscriptions WHERE email = $1", email.as_ref())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Erases `email` whether or not it is still subscribed: a subscriber who left may still appear
+/// in the delivery logs.
+#[tracing::instrument(name = "Erasing a subscriber", skip(pool))]
+async fn erase_subscriber(pool: &PgPool, email: &SubscriberEmail) -> Result<(), sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let subscriber_ids: Vec<_> = sqlx::query!(
+        r#"
+    SELECT subscriber_id FROM issue_deliveries WHERE subscriber_email = $1
+    UNION
+    SELECT id FROM subscriptions WHERE email = $1
+    "#,
+        email.as_ref()
+    )
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .filter_map(|row| row.subscriber_id)
+    .collect();
+    for subscriber_id in subscriber_ids {
+        sqlx::query!(
+            "DELETE FROM tracking_events WHERE subscriber_id = $1",
+            subscriber_id
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    sqlx::query!(
+        "DELETE FROM issue_delivery_queue WHERE subscriber_email = $1",
+        email.as_ref()
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM issue_deliveries WHERE subscriber_email = $1",
+        email.as_ref()
+    )
+    .execute(&mut transaction)
+    .await?;
+    sqlx::query!("DELETE FROM email_events WHERE email = $1", email.as_ref())
+        .execute(&mut transaction)
+        .await?;
+    sqlx::query!("DELETE FROM subscriptions WHERE email = $1", email.as_ref())
+        .execute(&mut transaction)
+        .await?;
+    suppress(
+        &mut transaction,
+        &SuppressionEntry::address(email),
+        SuppressionReason::Erasure,
+        None,
+    )
+    .await?;
+    transaction.commit().await
+}
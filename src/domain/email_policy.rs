@@ -0,0 +1,252 @@
+use std::{collections::HashSet, time::Duration};
+
+use super::{
+    mail_domain::{suggest_domain, DnsResolver, MailDomainResolver},
+    SubscriberEmail, SubscriberEmailError,
+};
+use crate::configuration::EmailPolicySettings;
+
+//NOTE: One domain per line, `#` starts a comment. Refresh it from
+//https://github.com/disposable-email-domains/disposable-email-domains when needed.
+const BUNDLED_DISPOSABLE_DOMAINS: &str = include_str!("disposable_domains.txt");
+
+/// Mailboxes that belong to a function rather than to a person: nobody reads the newsletter
+/// there, and complaints from them hurt our sender reputation.
+const ROLE_ACCOUNTS: [&str; 10] = [
+    "abuse",
+    "admin",
+    "hostmaster",
+    "mailer-daemon",
+    "no-reply",
+    "noreply",
+    "postmaster",
+    "root",
+    "webmaster",
+    "www",
+];
+
+/// Rules on top of [`SubscriberEmail::parse`] deciding which addresses we accept subscriptions
+/// from.
+pub struct EmailPolicy {
+    disposable_domains: HashSet<String>,
+    reject_role_accounts: bool,
+    mail_domain_resolver: Option<Box<dyn MailDomainResolver>>,
+}
+
+impl EmailPolicy {
+    /// Builds the policy, reading `blocklist_path` (if any) on top of the bundled blocklist.
+    pub fn new(settings: &EmailPolicySettings) -> Result<Self, std::io::Error> {
+        let mut disposable_domains = HashSet::new();
+        if settings.reject_disposable {
+            disposable_domains.extend(parse_blocklist(BUNDLED_DISPOSABLE_DOMAINS));
+            if let Some(path) = &settings.blocklist_path {
+                disposable_domains.extend(parse_blocklist(&std::fs::read_to_string(path)?));
+            }
+        }
+        let mail_domain_resolver = if settings.verify_domain {
+            let timeout = Duration::from_millis(settings.dns_timeout_milliseconds);
+            let resolver = DnsResolver::from_system_conf(timeout).map_err(std::io::Error::other)?;
+            Some(Box::new(resolver) as Box<dyn MailDomainResolver>)
+        } else {
+            None
+        };
+        Ok(Self {
+            disposable_domains,
+            reject_role_accounts: settings.reject_role_accounts,
+            mail_domain_resolver,
+        })
+    }
+
+    /// Verifies domains with `resolver` rather than with the one picked by [`EmailPolicy::new`].
+    pub fn with_mail_domain_resolver(
+        mut self,
+        resolver: impl MailDomainResolver + 'static,
+    ) -> Self {
+        self.mail_domain_resolver = Some(Box::new(resolver));
+        self
+    }
+
+    pub async fn check(&self, email: &SubscriberEmail) -> Result<(), SubscriberEmailError> {
+        //NOTE: Blocking a domain blocks its subdomains as well
+        let domain = email.domain();
+        let blocked = std::iter::successors(Some(domain), |d| d.split_once('.').map(|(_, p)| p))
+            .any(|d| self.disposable_domains.contains(d));
+        if blocked {
+            return Err(SubscriberEmailError::DisposableDomain(domain.to_string()));
+        }
+
+        //NOTE: `user+tag@` is routed to `user@` by most providers
+        let local_part = email.local_part().to_lowercase();
+        let mailbox = local_part.split('+').next().unwrap_or_default();
+        if self.reject_role_accounts && ROLE_ACCOUNTS.contains(&mailbox) {
+            return Err(SubscriberEmailError::RoleAccount(mailbox.to_string()));
+        }
+
+        //NOTE: Checked whatever DNS says: typo domains are often registered to catch the emails
+        //meant for the provider.
+        if let Some(provider) = suggest_domain(domain) {
+            return Err(SubscriberEmailError::LikelyTypo {
+                domain: domain.to_string(),
+                suggestion: format!("{}@{}", email.local_part(), provider),
+            });
+        }
+
+        if let Some(resolver) = &self.mail_domain_resolver {
+            match resolver.accepts_mail(domain).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    return Err(SubscriberEmailError::UndeliverableDomain(
+                        domain.to_string(),
+                    ))
+                }
+                //NOTE: A flaky nameserver should not stop people from subscribing
+                Err(e) => tracing::warn!(
+                    error.cause_chain = ?e,
+                    domain,
+                    "Failed to verify the email domain, accepting it"
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_blocklist(contents: &str) -> impl Iterator<Item = String> + '_ {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| idna::domain_to_ascii(line).unwrap_or_else(|_| line.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use claim::assert_ok;
+
+    use super::EmailPolicy;
+    use crate::{
+        configuration::EmailPolicySettings,
+        domain::{
+            mail_domain::{MailDomainResolver, ResolveFuture},
+            SubscriberEmail, SubscriberEmailError,
+        },
+    };
+
+    /// Only `example.com` and `gmial.com` accept emails, `flaky.example` cannot be resolved.
+    struct StubResolver;
+
+    impl MailDomainResolver for StubResolver {
+        fn accepts_mail<'a>(&'a self, domain: &'a str) -> ResolveFuture<'a> {
+            Box::pin(async move {
+                match domain {
+                    "flaky.example" => Err("timed out".into()),
+                    domain => Ok(domain == "example.com" || domain == "gmial.com"),
+                }
+            })
+        }
+    }
+
+    fn settings() -> EmailPolicySettings {
+        EmailPolicySettings {
+            reject_disposable: true,
+            blocklist_path: None,
+            reject_role_accounts: false,
+            verify_domain: false,
+            dns_timeout_milliseconds: 1000,
+        }
+    }
+
+    fn policy(reject_role_accounts: bool) -> EmailPolicy {
+        EmailPolicy::new(&EmailPolicySettings {
+            reject_role_accounts,
+            ..settings()
+        })
+        .unwrap()
+    }
+
+    fn email(s: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(s.to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn disposable_domains_and_their_subdomains_are_rejected() {
+        for address in ["ursula@mailinator.com", "ursula@eu.Mailinator.com"] {
+            assert_eq!(
+                policy(false).check(&email(address)).await,
+                Err(SubscriberEmailError::DisposableDomain(
+                    email(address).domain().to_string()
+                ))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn role_accounts_are_only_rejected_when_enabled() {
+        assert_ok!(policy(false).check(&email("postmaster@example.com")).await);
+        assert_eq!(
+            policy(true).check(&email("NoReply+news@example.com")).await,
+            Err(SubscriberEmailError::RoleAccount("noreply".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn personal_addresses_are_accepted() {
+        assert_ok!(policy(true).check(&email("ursula@example.com")).await);
+    }
+
+    #[tokio::test]
+    async fn domains_that_do_not_accept_emails_are_rejected() {
+        let policy = policy(false).with_mail_domain_resolver(StubResolver);
+
+        assert_ok!(policy.check(&email("ursula@example.com")).await);
+        assert_eq!(
+            policy.check(&email("ursula@nowhere.example")).await,
+            Err(SubscriberEmailError::UndeliverableDomain(
+                "nowhere.example".into()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn typos_of_common_providers_are_rejected_with_a_suggestion_whatever_dns_says() {
+        let expected = Err(SubscriberEmailError::LikelyTypo {
+            domain: "gmial.com".into(),
+            suggestion: "ursula@gmail.com".into(),
+        });
+        let verified = policy(false).with_mail_domain_resolver(StubResolver);
+        assert_eq!(verified.check(&email("ursula@gmial.com")).await, expected);
+        assert_eq!(
+            policy(false).check(&email("ursula@gmial.com")).await,
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn domains_that_cannot_be_resolved_are_accepted() {
+        let policy = policy(false).with_mail_domain_resolver(StubResolver);
+        assert_ok!(policy.check(&email("ursula@flaky.example")).await);
+    }
+
+    #[tokio::test]
+    async fn the_blocklist_file_extends_the_bundled_one() {
+        let path = std::env::temp_dir().join(format!("blocklist-{}.txt", uuid::Uuid::new_v4()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(
+            file,
+            "# our own additions\nspam.example  # seen in the wild"
+        )
+        .unwrap();
+
+        let policy = EmailPolicy::new(&EmailPolicySettings {
+            blocklist_path: Some(path.clone()),
+            ..settings()
+        })
+        .unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(policy.check(&email("ursula@spam.example")).await.is_err());
+        assert!(policy.check(&email("ursula@mailinator.com")).await.is_err());
+    }
+}
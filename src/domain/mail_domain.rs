@@ -0,0 +1,154 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use hickory_resolver::{
+    error::{ResolveError, ResolveErrorKind},
+    proto::op::ResponseCode,
+    TokioAsyncResolver,
+};
+
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<bool, ResolveError>> + Send + 'a>>;
+
+/// Tells whether a domain can receive emails.
+///
+/// Implemented by [`DnsResolver`]; tests swap it for a stub so that they do not depend on the
+/// network.
+pub trait MailDomainResolver: Send + Sync {
+    /// Resolves to `false` when the domain does not exist or explicitly refuses emails, and to an
+    /// error when we could not find out (e.g. the nameserver timed out).
+    fn accepts_mail<'a>(&'a self, domain: &'a str) -> ResolveFuture<'a>;
+}
+
+pub struct DnsResolver {
+    resolver: TokioAsyncResolver,
+}
+
+impl DnsResolver {
+    /// Uses the nameservers from `/etc/resolv.conf`.
+    pub fn from_system_conf(timeout: Duration) -> Result<Self, ResolveError> {
+        let (config, mut options) = hickory_resolver::system_conf::read_system_conf()?;
+        options.timeout = timeout;
+        options.attempts = 1;
+        Ok(Self {
+            resolver: TokioAsyncResolver::tokio(config, options),
+        })
+    }
+
+    async fn lookup(&self, domain: &str) -> Result<bool, ResolveError> {
+        //NOTE: Trailing dot, otherwise the resolver tries the search domains from resolv.conf too
+        let domain = format!("{}.", domain.trim_end_matches('.'));
+        match self.resolver.mx_lookup(domain.as_str()).await {
+            //NOTE: A single MX pointing to `.` is a "null MX" (RFC 7505): the domain takes no email
+            Ok(mx) => Ok(mx.iter().any(|mx| !mx.exchange().is_root())),
+            Err(e) if is_nx_domain(&e) => Ok(false),
+            //NOTE: Without MX records, mail goes to the A/AAAA records of the domain (RFC 5321)
+            Err(e) if is_no_records(&e) => match self.resolver.lookup_ip(domain.as_str()).await {
+                Ok(ips) => Ok(ips.iter().next().is_some()),
+                Err(e) if is_no_records(&e) => Ok(false),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl MailDomainResolver for DnsResolver {
+    fn accepts_mail<'a>(&'a self, domain: &'a str) -> ResolveFuture<'a> {
+        Box::pin(self.lookup(domain))
+    }
+}
+
+fn is_no_records(e: &ResolveError) -> bool {
+    matches!(e.kind(), ResolveErrorKind::NoRecordsFound { .. })
+}
+
+fn is_nx_domain(e: &ResolveError) -> bool {
+    matches!(
+        e.kind(),
+        ResolveErrorKind::NoRecordsFound {
+            response_code: ResponseCode::NXDomain,
+            ..
+        }
+    )
+}
+
+/// Providers our subscribers use the most, used to spot typos such as `gmial.com`.
+///
+/// Typos are rejected, so providers a couple of edits away from another one (`gmx.net` and
+/// `gmx.de`) must all be listed.
+const COMMON_PROVIDERS: [&str; 30] = [
+    "aol.com",
+    "comcast.net",
+    "gmail.com",
+    "gmx.at",
+    "gmx.com",
+    "gmx.de",
+    "gmx.net",
+    "googlemail.com",
+    "hotmail.co.uk",
+    "hotmail.com",
+    "hotmail.de",
+    "hotmail.fr",
+    "icloud.com",
+    "libero.it",
+    "live.com",
+    "live.fr",
+    "mail.com",
+    "me.com",
+    "msn.com",
+    "orange.fr",
+    "outlook.com",
+    "proton.me",
+    "protonmail.com",
+    "web.de",
+    "yahoo.co.uk",
+    "yahoo.com",
+    "yahoo.de",
+    "yahoo.fr",
+    "yandex.com",
+    "ymail.com",
+];
+
+/// Suggests the common provider `domain` is most likely a typo of, if any.
+pub fn suggest_domain(domain: &str) -> Option<&'static str> {
+    if COMMON_PROVIDERS.contains(&domain) {
+        return None;
+    }
+    COMMON_PROVIDERS
+        .iter()
+        .map(|provider| (provider, strsim::damerau_levenshtein(domain, provider)))
+        //NOTE: Past two edits we are more likely to be looking at an unrelated domain
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(provider, _)| *provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::suggest_domain;
+
+    #[test]
+    fn typos_of_common_providers_get_a_suggestion() {
+        for (typo, expected) in [
+            ("gmial.com", "gmail.com"),
+            ("gmail.co", "gmail.com"),
+            ("hotmal.com", "hotmail.com"),
+            ("yahooo.com", "yahoo.com"),
+            ("outlok.com", "outlook.com"),
+        ] {
+            assert_eq!(suggest_domain(typo), Some(expected), "{}", typo);
+        }
+    }
+
+    #[test]
+    fn known_and_unrelated_domains_get_no_suggestion() {
+        for domain in [
+            "gmail.com",
+            "gmx.net",
+            "mail.com",
+            "example.com",
+            "zero2prod.dev",
+        ] {
+            assert_eq!(suggest_domain(domain), None, "{}", domain);
+        }
+    }
+}
@@ -1,54 +1,85 @@
+mod email_policy;
+pub use email_policy::EmailPolicy;
+
+mod mail_domain;
+pub use mail_domain::{DnsResolver, MailDomainResolver, ResolveFuture};
+
 mod new_subscriber;
 pub use new_subscriber::NewSubscriber;
 
 mod subscriber_email;
-pub use subscriber_email::SubscriberEmail;
+pub use subscriber_email::{SubscriberEmail, SubscriberEmailError};
 
 mod subscriber_name;
-pub use subscriber_name::SubscriberName;
+pub use subscriber_name::{SubscriberName, SubscriberNameError, UnvalidatedSubscriberName};
 
 #[cfg(test)]
 mod tests {
     use claim::{assert_err, assert_ok};
 
-    use crate::domain::SubscriberName;
+    use crate::{
+        configuration::SubscriberNameSettings,
+        domain::{SubscriberName, SubscriberNameError},
+    };
+
+    fn rules() -> SubscriberNameSettings {
+        SubscriberNameSettings {
+            max_graphemes: 256,
+            forbidden_chars: "/()\"<>\\{}".into(),
+            normalize_unicode: true,
+            trim_whitespace: true,
+        }
+    }
 
     #[test]
     fn a_256_grapheme_long_name_is_valid() {
         let name = "a".repeat(256);
-        assert_ok!(SubscriberName::parse(name));
+        assert_ok!(SubscriberName::parse(name, &rules()));
     }
 
     #[test]
     fn a_256_graphemes_long_name_is_invalid() {
         let name = "a".repeat(257);
         //WARN: `claim` needs our type to implement `Debug` trait to provide nice error messages.
-        assert_err!(SubscriberName::parse(name));
+        assert_err!(SubscriberName::parse(name.clone(), &rules()));
+        assert_eq!(
+            SubscriberName::parse(name, &rules()).unwrap_err(),
+            SubscriberNameError::TooLong {
+                graphemes: 257,
+                max: 256
+            }
+        );
     }
 
     #[test]
     fn whitespace_only_names_are_rejected() {
         let name = " ".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_eq!(
+            SubscriberName::parse(name, &rules()).unwrap_err(),
+            SubscriberNameError::Empty
+        );
     }
 
     #[test]
     fn empty_string_is_rejected() {
         let name = "".to_string();
-        assert_err!(SubscriberName::parse(name));
+        assert_err!(SubscriberName::parse(name, &rules()));
     }
 
     #[test]
     fn names_containing_invalid_chars_are_rejected() {
-        for name in &['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
-            let name = name.to_string();
-            assert_err!(SubscriberName::parse(name));
+        for c in ['/', '(', ')', '"', '<', '>', '\\', '{', '}'] {
+            let name = format!("Ursula {}", c);
+            assert_eq!(
+                SubscriberName::parse(name, &rules()).unwrap_err(),
+                SubscriberNameError::ForbiddenChar(c)
+            );
         }
     }
 
     #[test]
     fn a_valid_name_is_parsed_successfully() {
         let name = "Ursula Le Guin".to_string();
-        assert_ok!(SubscriberName::parse(name));
+        assert_ok!(SubscriberName::parse(name, &rules()));
     }
 }
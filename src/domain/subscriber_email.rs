@@ -1,24 +1,155 @@
+use std::fmt;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
 use validator::validate_email;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
-    pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if validate_email(&s) {
-            Ok(SubscriberEmail(s))
-        } else {
-            Err("Invalid subscriber email".to_string())
+    /// Validates `s` and normalizes its domain: it is lowercased and, for internationalized
+    /// domains, converted to punycode. The local part is kept as is, providers are free to treat
+    /// it as case sensitive.
+    pub fn parse(s: String) -> Result<SubscriberEmail, SubscriberEmailError> {
+        if s.trim().is_empty() {
+            return Err(SubscriberEmailError::Empty);
+        }
+        if !validate_email(&s) {
+            return Err(SubscriberEmailError::InvalidFormat);
+        }
+        //NOTE: `validate_email` guarantees there is an `@`, the local part may contain more when
+        //quoted so we split on the last one
+        let (local_part, domain) = s.rsplit_once('@').unwrap();
+        let domain = idna::domain_to_ascii(domain)
+            .map_err(|_| SubscriberEmailError::InvalidDomain(domain.to_string()))?;
+        Ok(SubscriberEmail(format!("{}@{}", local_part, domain)))
+    }
+
+    pub fn local_part(&self) -> &str {
+        self.0.rsplit_once('@').unwrap().0
+    }
+
+    /// The ASCII (punycode) form of the domain, always lowercase.
+    pub fn domain(&self) -> &str {
+        self.0.rsplit_once('@').unwrap().1
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SubscriberEmailError {
+    Empty,
+    InvalidFormat,
+    InvalidDomain(String),
+    DisposableDomain(String),
+    RoleAccount(String),
+    UndeliverableDomain(String),
+    /// The domain is a typo of a common provider, such as `gmial.com`.
+    LikelyTypo {
+        domain: String,
+        /// The address the subscriber most likely meant.
+        suggestion: String,
+    },
+}
+
+impl SubscriberEmailError {
+    /// A stable identifier for the error, for clients to pick their own (translated) message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::InvalidFormat => "invalid_format",
+            Self::InvalidDomain(_) => "invalid_domain",
+            Self::DisposableDomain(_) => "disposable_domain",
+            Self::RoleAccount(_) => "role_account",
+            Self::UndeliverableDomain(_) => "undeliverable_domain",
+            Self::LikelyTypo { .. } => "likely_typo",
         }
     }
 }
 
+impl fmt::Display for SubscriberEmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The subscriber email cannot be empty"),
+            Self::InvalidFormat => write!(f, "Invalid subscriber email"),
+            Self::InvalidDomain(domain) => write!(f, "{} is not a valid email domain", domain),
+            Self::DisposableDomain(domain) => write!(
+                f,
+                "{} is a disposable email provider, please use a permanent address",
+                domain
+            ),
+            Self::RoleAccount(local_part) => write!(
+                f,
+                "{}@ addresses belong to a role rather than a person, please use a personal address",
+                local_part
+            ),
+            Self::UndeliverableDomain(domain) => write!(f, "{} does not accept emails", domain),
+            Self::LikelyTypo { domain, suggestion } => write!(
+                f,
+                "{} looks like a typo, did you mean {}?",
+                domain, suggestion
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SubscriberEmailError {}
+
 impl AsRef<str> for SubscriberEmail {
     fn as_ref(&self) -> &str {
         &self.0
     }
 }
 
+impl fmt::Display for SubscriberEmail {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for SubscriberEmail {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for SubscriberEmail {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::parse(s).map_err(de::Error::custom)
+    }
+}
+
+//NOTE: Stored as `TEXT`. Decoding goes through `parse` too, so a row edited by hand cannot smuggle
+//an invalid address into the application.
+impl Type<Postgres> for SubscriberEmail {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Postgres> for SubscriberEmail {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<'q, Postgres>>::encode(self.0.as_str(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for SubscriberEmail {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let s = <String as Decode<'r, Postgres>>::decode(value)?;
+        Ok(Self::parse(s)?)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use claim::{assert_err, assert_ok};
@@ -40,13 +171,19 @@ mod tests {
     #[test]
     fn empty_string_is_rejected() {
         let email = "".to_string();
-        assert_err!(SubscriberEmail::parse(email), "Email is too short");
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            SubscriberEmailError::Empty
+        );
     }
 
     #[test]
     fn email_missing_at_symbol() {
         let email = "somethingdomain.com".to_string();
-        assert_err!(SubscriberEmail::parse(email));
+        assert_eq!(
+            SubscriberEmail::parse(email).unwrap_err(),
+            SubscriberEmailError::InvalidFormat
+        );
     }
 
     #[test]
@@ -63,6 +200,25 @@ mod tests {
         assert_ok!(SubscriberEmail::parse(email));
     }
 
+    #[test]
+    fn the_domain_is_lowercased_but_not_the_local_part() {
+        let email = SubscriberEmail::parse("Ursula@Example.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula@example.com");
+    }
+
+    #[test]
+    fn internationalized_domains_are_converted_to_punycode() {
+        let email = SubscriberEmail::parse("ursula@bücher.example".to_string()).unwrap();
+        assert_eq!(email.domain(), "xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn deserialization_validates_the_address() {
+        let email: SubscriberEmail = serde_json::from_str(r#""ursula@Example.com""#).unwrap();
+        assert_eq!(email.as_ref(), "ursula@example.com");
+        assert_err!(serde_json::from_str::<SubscriberEmail>(r#""ursula""#));
+    }
+
     #[quickcheck_macros::quickcheck]
     fn valid_emails_are_parsed_succesfully(valid: ValidEmailFixture) -> bool {
         SubscriberEmail::parse(valid.0).is_ok()
@@ -1,38 +1,66 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use unicode_normalization::UnicodeNormalization;
 use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(Debug)]
+use crate::configuration::SubscriberNameSettings;
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct SubscriberName(String);
 
 impl SubscriberName {
-    /// Returns an instance of [`SubscriberName`] if the input satisfies all our validation
-    /// constraints on subscriber name
+    /// Returns an instance of [`SubscriberName`] if the input satisfies the rules in `rules`.
+    ///
+    /// Depending on `rules`, surrounding whitespace is trimmed and the name is normalized to NFC
+    /// before being checked: the returned name is the one that should be stored.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if the input string is either empty, longer than 256 graphemes or contains
-    /// forbidden_chars chars
-    pub fn parse(s: String) -> Result<Self, String> {
-        let is_empty_or_whitespace = s.trim().is_empty();
+    /// Returns a [`SubscriberNameError`] if the input is empty (or only whitespace), longer than
+    /// `rules.max_graphemes`, or contains one of `rules.forbidden_chars`.
+    pub fn parse(s: String, rules: &SubscriberNameSettings) -> Result<Self, SubscriberNameError> {
+        let s = if rules.trim_whitespace {
+            s.trim().to_string()
+        } else {
+            s
+        };
+        //NOTE: "é" can be written as a single code point or as "e" followed by a combining accent:
+        //NFC picks the former, so that the same name is always stored the same way
+        let s = if rules.normalize_unicode {
+            s.nfc().collect()
+        } else {
+            s
+        };
+
+        if s.trim().is_empty() {
+            return Err(SubscriberNameError::Empty);
+        }
 
         //NOTE: A grapheme is defined by the Unicode standard as a "user-perceived" character: an
         //example would be an Umlaut letter, but it is composed of two characters
         //`graphemes` returns an iterator over the graphemes in the input `s`. `true` specifies that we
         //want to use the extended grapheme definition set, the recommended one
-        let is_too_long = s.graphemes(true).count() > 256;
-
-        let forbidden_chars = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-        let contains_forbidden_chars = s.chars().any(|g| forbidden_chars.contains(&g));
+        let graphemes = s.graphemes(true).count();
+        if graphemes > rules.max_graphemes {
+            return Err(SubscriberNameError::TooLong {
+                graphemes,
+                max: rules.max_graphemes,
+            });
+        }
 
-        if is_empty_or_whitespace || is_too_long || contains_forbidden_chars {
-            Err(format!("{} is not a valid subscriber name. ", s))
-        } else {
-            Ok(Self(s))
+        if let Some(c) = s.chars().find(|c| rules.forbidden_chars.contains(*c)) {
+            return Err(SubscriberNameError::ForbiddenChar(c));
         }
-    }
 
-    // pub fn inner_ref(&self) -> &str {
-    //     &self.0
-    // }
+        Ok(Self(s))
+    }
 }
 
 impl AsRef<str> for SubscriberName {
@@ -40,3 +68,171 @@ impl AsRef<str> for SubscriberName {
         &self.0
     }
 }
+
+impl fmt::Display for SubscriberName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for SubscriberName {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl Type<Postgres> for SubscriberName {
+    fn type_info() -> PgTypeInfo {
+        <String as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <String as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Postgres> for SubscriberName {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<'q, Postgres>>::encode(self.0.as_str(), buf)
+    }
+}
+
+//NOTE: Unlike emails, stored names are not validated again: the rules are configurable and may
+//have been tightened since the row was written, which must not make existing subscribers unreadable.
+impl<'r> Decode<'r, Postgres> for SubscriberName {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        <String as Decode<'r, Postgres>>::decode(value).map(Self)
+    }
+}
+
+/// A subscriber name as found in a request body, to be checked with [`Self::validate`].
+///
+/// Unlike emails, names cannot be validated while deserializing: the rules are settings, registered
+/// as application data, that only the handler has access to.
+#[derive(Debug, Deserialize)]
+#[serde(transparent)]
+pub struct UnvalidatedSubscriberName(String);
+
+impl UnvalidatedSubscriberName {
+    /// See [`SubscriberName::parse`].
+    pub fn validate(
+        self,
+        rules: &SubscriberNameSettings,
+    ) -> Result<SubscriberName, SubscriberNameError> {
+        SubscriberName::parse(self.0, rules)
+    }
+}
+
+impl fmt::Display for UnvalidatedSubscriberName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SubscriberNameError {
+    Empty,
+    TooLong { graphemes: usize, max: usize },
+    ForbiddenChar(char),
+}
+
+impl SubscriberNameError {
+    /// A stable identifier for the error, for clients to pick their own (translated) message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "empty",
+            Self::TooLong { .. } => "too_long",
+            Self::ForbiddenChar(_) => "forbidden_char",
+        }
+    }
+}
+
+impl fmt::Display for SubscriberNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "The subscriber name cannot be empty"),
+            Self::TooLong { graphemes, max } => write!(
+                f,
+                "The subscriber name is {} characters long, the maximum is {}",
+                graphemes, max
+            ),
+            Self::ForbiddenChar(c) => {
+                write!(f, "The subscriber name cannot contain `{}`", c)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SubscriberNameError {}
+
+#[cfg(test)]
+mod tests {
+    use unicode_segmentation::UnicodeSegmentation;
+
+    use super::*;
+
+    fn rules(trim_whitespace: bool, normalize_unicode: bool) -> SubscriberNameSettings {
+        SubscriberNameSettings {
+            max_graphemes: 16,
+            forbidden_chars: "/()\"<>\\{}".into(),
+            normalize_unicode,
+            trim_whitespace,
+        }
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed_when_enabled() {
+        let name = SubscriberName::parse("  Ursula ".into(), &rules(true, false)).unwrap();
+        assert_eq!(name.as_ref(), "Ursula");
+
+        let name = SubscriberName::parse("  Ursula ".into(), &rules(false, false)).unwrap();
+        assert_eq!(name.as_ref(), "  Ursula ");
+    }
+
+    #[test]
+    fn names_are_normalized_to_nfc_when_enabled() {
+        let decomposed = "Rene\u{301}e".to_string();
+        let name = SubscriberName::parse(decomposed, &rules(false, true)).unwrap();
+        assert_eq!(name.as_ref(), "Ren\u{e9}e");
+    }
+
+    #[test]
+    fn submitted_names_are_validated_against_the_given_rules() {
+        let name: UnvalidatedSubscriberName = serde_json::from_str(r#"" Ursula ""#).unwrap();
+        assert_eq!(
+            name.validate(&rules(true, false)).unwrap().as_ref(),
+            "Ursula"
+        );
+
+        let name: UnvalidatedSubscriberName = serde_json::from_str(r#""{Ursula}""#).unwrap();
+        assert_eq!(
+            name.validate(&rules(true, false)),
+            Err(SubscriberNameError::ForbiddenChar('{'))
+        );
+    }
+
+    //NOTE: What we store must be accepted, unchanged, when read back and validated again
+    #[quickcheck_macros::quickcheck]
+    fn stored_names_validate_to_themselves(s: String, trim: bool, normalize: bool) -> bool {
+        let rules = rules(trim, normalize);
+        match SubscriberName::parse(s, &rules) {
+            Ok(name) => SubscriberName::parse(name.as_ref().to_string(), &rules) == Ok(name),
+            Err(_) => true,
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn stored_names_satisfy_the_rules(s: String, trim: bool, normalize: bool) -> bool {
+        let rules = rules(trim, normalize);
+        match SubscriberName::parse(s, &rules) {
+            Ok(name) => {
+                let name = name.as_ref();
+                !name.trim().is_empty()
+                    && name.graphemes(true).count() <= rules.max_graphemes
+                    && !name.chars().any(|c| rules.forbidden_chars.contains(c))
+                    && (!trim || name.trim() == name)
+            }
+            Err(_) => true,
+        }
+    }
+}
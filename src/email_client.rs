@@ -1,28 +1,150 @@
+use std::{fmt, sync::RwLock};
+
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::{domain::SubscriberEmail, suppressions::is_suppressed};
 
-use crate::domain::SubscriberEmail;
+/// Gmail hides everything past this many bytes of HTML behind a "[Message clipped]" link, the
+/// unsubscribe link in the footer included.
+pub const GMAIL_CLIPPING_THRESHOLD_BYTES: usize = 102 * 1024;
 
 #[derive(Debug)]
 pub struct EmailClient {
-    sender: SubscriberEmail,
     base_url: String,
     http_client: reqwest::Client,
+    //NOTE: Sender and token can be swapped at runtime (see `reload`), requests that are already
+    //in flight keep using the values they were built with.
+    credentials: RwLock<Credentials>,
+    /// Checked before every email, see [`EmailClient::with_suppression_list`].
+    suppressions: Option<PgPool>,
+}
+
+#[derive(Debug)]
+struct Credentials {
+    sender: SubscriberEmail,
     authorization_token: Secret<String>,
 }
 
+#[derive(Debug)]
+pub enum SendEmailError {
+    /// The recipient, or their domain, is on the suppression list: nothing was sent.
+    Suppressed,
+    /// The suppression list could not be checked: nothing was sent.
+    Database(sqlx::Error),
+    /// The provider could not be reached, or refused the email.
+    Request(reqwest::Error),
+}
+
+impl fmt::Display for SendEmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Suppressed => write!(f, "The recipient is on the suppression list"),
+            Self::Database(_) => write!(f, "Failed to check the suppression list"),
+            Self::Request(e) => write!(f, "Failed to send the email: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SendEmailError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Suppressed => None,
+            Self::Database(e) => Some(e),
+            Self::Request(e) => Some(e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SendEmailError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+impl From<reqwest::Error> for SendEmailError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(e)
+    }
+}
+
+/// Who an email is from, when not from the sender the client was configured with: see
+/// [`EmailClient::send_email_from`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sender {
+    pub email: SubscriberEmail,
+    /// Shown by mail clients instead of the address. Must not contain control characters.
+    pub name: Option<String>,
+}
+
+/// `"Name" <address>`, as expected in a `From` header, or the bare address.
+impl fmt::Display for Sender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => {
+                let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{}\" <{}>", quoted, self.email.as_ref())
+            }
+            None => write!(f, "{}", self.email.as_ref()),
+        }
+    }
+}
+
+/// What the provider told us about an email it accepted.
+#[derive(Debug, Default)]
+pub struct SentEmail {
+    /// The provider's id for the email, which its bounce and complaint reports refer to. `None`
+    /// when the response does not carry one.
+    pub message_id: Option<String>,
+}
+
 impl EmailClient {
     pub async fn send_email(
         &self,
-        recipient: SubscriberEmail,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<SentEmail, SendEmailError> {
+        self.send_email_from(None, recipient, subject, html_content, text_content)
+            .await
+    }
+
+    /// Sends the email on behalf of `sender` rather than the configured sender, e.g. the sender
+    /// of a list. The provider must accept `sender` as a sender signature.
+    pub async fn send_email_from(
+        &self,
+        sender: Option<&Sender>,
+        recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
+    ) -> Result<SentEmail, SendEmailError> {
+        if let Some(pool) = &self.suppressions {
+            if is_suppressed(pool, recipient).await? {
+                return Err(SendEmailError::Suppressed);
+            }
+        }
+        if html_content.len() > GMAIL_CLIPPING_THRESHOLD_BYTES {
+            tracing::warn!(
+                html_size_bytes = html_content.len(),
+                subject,
+                "The email is large enough to be clipped by Gmail"
+            );
+        }
         let url = format!("{}/email", self.base_url);
+        //WARN: The guard must be dropped before the first `.await`
+        let (default_sender, authorization_token) = {
+            let credentials = self.credentials.read().unwrap();
+            (
+                credentials.sender.as_ref().to_string(),
+                credentials.authorization_token.clone(),
+            )
+        };
         let request_body = SendEmailRequest {
-            from: self.sender.as_ref().to_string(),
+            from: sender.map_or(default_sender, Sender::to_string),
             to: recipient.as_ref().to_string(),
             subject: subject.to_string(),
             text_body: text_content.to_string(),
@@ -30,17 +152,23 @@ impl EmailClient {
         };
         //NOTE: The `json` method goes a bit further than simple serialization: it will also set
         //the `Content-type` header to `application/json` - matching what we saw in the example
-        let builder = self
+        let response = self
             .http_client
             .post(url)
             .header(
                 "X-Postmark-Server-Token",
-                self.authorization_token.expose_secret(),
+                authorization_token.expose_secret(),
             )
             .json(&request_body)
             .send()
-            .await?;
-        Ok(())
+            .await?
+            .error_for_status()?;
+        //NOTE: The email is sent at this point, an unexpected body only costs us its id
+        let body = response.bytes().await?;
+        let message_id = serde_json::from_slice::<SendEmailResponse>(&body)
+            .ok()
+            .and_then(|response| response.message_id);
+        Ok(SentEmail { message_id })
     }
 
     pub fn new(
@@ -49,12 +177,30 @@ impl EmailClient {
         authorization_token: Secret<String>,
     ) -> Self {
         Self {
-            sender,
             base_url,
             http_client: Client::new(),
-            authorization_token,
+            credentials: RwLock::new(Credentials {
+                sender,
+                authorization_token,
+            }),
+            suppressions: None,
         }
     }
+
+    /// Refuses to send anything to the addresses, and domains, on the suppression list stored in
+    /// `pool`. Every client sending on behalf of the application should have one.
+    pub fn with_suppression_list(mut self, pool: PgPool) -> Self {
+        self.suppressions = Some(pool);
+        self
+    }
+
+    /// Swaps sender and authorization token used for all subsequent emails.
+    pub fn update_credentials(&self, sender: SubscriberEmail, authorization_token: Secret<String>) {
+        *self.credentials.write().unwrap() = Credentials {
+            sender,
+            authorization_token,
+        };
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -66,6 +212,12 @@ struct SendEmailRequest {
     text_body: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use fake::{
@@ -78,11 +230,14 @@ mod tests {
     use secrecy::Secret;
     use serde_json::Value;
     use wiremock::{
-        matchers::{any, header, header_exists, method, path},
+        matchers::{body_partial_json, header, header_exists, method, path},
         Mock, MockServer, Request, ResponseTemplate,
     };
 
-    use crate::{domain::SubscriberEmail, email_client::EmailClient};
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{EmailClient, Sender},
+    };
 
     struct SendEmailBodyMatcher;
 
@@ -125,7 +280,88 @@ mod tests {
             .await;
 
         let _ = email_client
-            .send_email(subscriber_email, &subject, &content, &content)
+            .send_email(&subscriber_email, &subject, &content, &content)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_uses_updated_credentials() {
+        let mock_server = MockServer::start().await;
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let email_client = EmailClient::new(mock_server.uri(), sender, Secret::new(Faker.fake()));
+
+        let new_sender: String = SafeEmail().fake();
+        email_client.update_credentials(
+            SubscriberEmail::parse(new_sender.clone()).unwrap(),
+            Secret::new("rotated-token".to_string()),
+        );
+
+        Mock::given(header("X-Postmark-Server-Token", "rotated-token"))
+            .and(body_partial_json(serde_json::json!({ "from": new_sender })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let _ = email_client
+            .send_email(&subscriber_email, "subject", "<p>content</p>", "content")
+            .await;
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_the_provider_message_id() {
+        let mock_server = MockServer::start().await;
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let email_client = EmailClient::new(mock_server.uri(), sender, Secret::new(Faker.fake()));
+        Mock::given(path("/email"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "MessageID": "b7bc2f4a-e38e-4336-af7d-e6c392c2f817",
+                "ErrorCode": 0,
+                "Message": "OK"
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let sent = email_client
+            .send_email(&subscriber_email, "subject", "<p>content</p>", "content")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sent.message_id.as_deref(),
+            Some("b7bc2f4a-e38e-4336-af7d-e6c392c2f817")
+        );
+    }
+
+    #[tokio::test]
+    async fn send_email_from_overrides_the_configured_sender() {
+        let mock_server = MockServer::start().await;
+        let sender = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let email_client = EmailClient::new(mock_server.uri(), sender, Secret::new(Faker.fake()));
+        let list_sender = Sender {
+            email: SubscriberEmail::parse("digest@example.com".into()).unwrap(),
+            name: Some(r#"The "Weekly" Digest"#.into()),
+        };
+
+        Mock::given(body_partial_json(serde_json::json!({
+            "from": r#""The \"Weekly\" Digest" <digest@example.com>"#
+        })))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+        let subscriber_email = SubscriberEmail::parse(SafeEmail().fake()).unwrap();
+        let _ = email_client
+            .send_email_from(
+                Some(&list_sender),
+                &subscriber_email,
+                "subject",
+                "<p>content</p>",
+                "content",
+            )
             .await;
     }
 }
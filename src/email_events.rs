@@ -0,0 +1,229 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    domain::SubscriberEmail,
+    suppressions::{suppress, SuppressionEntry, SuppressionReason},
+};
+
+/// The events reported by the provider that we act upon.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmailEventKind {
+    /// The address does not exist (anymore): the subscriber is marked as `bounced`, and the
+    /// address suppressed.
+    HardBounce,
+    /// A temporary failure (full mailbox, DNS hiccup...): recorded, the subscriber stays active.
+    SoftBounce,
+    /// The recipient flagged the email as spam: the subscriber is marked as `complained`, and the
+    /// address suppressed.
+    SpamComplaint,
+}
+
+impl EmailEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HardBounce => "hard_bounce",
+            Self::SoftBounce => "soft_bounce",
+            Self::SpamComplaint => "spam_complaint",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct EmailEvent {
+    /// The provider's id for the event, the same when an event is delivered more than once.
+    pub id: String,
+    pub kind: EmailEventKind,
+    pub email: String,
+    /// The id the provider gave the email when we sent it, see
+    /// [`SentEmail`](crate::email_client::SentEmail).
+    pub message_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+/// A Postmark webhook payload; spam complaints are bounces of type `SpamComplaint`. Only
+/// `RecordType` is common to every event, the rest is checked once we know we need it.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Payload {
+    record_type: String,
+    #[serde(rename = "ID", default)]
+    id: Option<serde_json::Value>,
+    #[serde(rename = "Type", default)]
+    bounce_type: Option<String>,
+    #[serde(default)]
+    email: Option<String>,
+    #[serde(rename = "MessageID", default)]
+    message_id: Option<String>,
+    #[serde(default)]
+    bounced_at: Option<DateTime<Utc>>,
+}
+
+/// Parses a webhook payload. `Ok(None)` for well-formed events we have no use for, such as
+/// deliveries or auto-responders: the provider should not retry those.
+pub fn parse_event(body: &[u8]) -> Result<Option<EmailEvent>, String> {
+    let payload: Payload = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let kind = match (payload.record_type.as_str(), payload.bounce_type.as_deref()) {
+        ("SpamComplaint", _) | ("Bounce", Some("SpamComplaint")) => EmailEventKind::SpamComplaint,
+        ("Bounce", Some("HardBounce" | "BadEmailAddress")) => EmailEventKind::HardBounce,
+        ("Bounce", Some("SoftBounce" | "Transient" | "DnsError")) => EmailEventKind::SoftBounce,
+        _ => return Ok(None),
+    };
+    let id = match payload.id {
+        Some(serde_json::Value::Number(id)) => id.to_string(),
+        Some(serde_json::Value::String(id)) if !id.is_empty() => id,
+        _ => return Err("`ID` must be a number or a non-empty string".into()),
+    };
+    Ok(Some(EmailEvent {
+        id,
+        kind,
+        email: payload.email.ok_or("`Email` is missing")?,
+        message_id: payload.message_id.filter(|id| !id.is_empty()),
+        occurred_at: payload.bounced_at.ok_or("`BouncedAt` is missing")?,
+    }))
+}
+
+/// Records the event and applies it: bounces are noted in the delivery log of the email they
+/// refer to, and the subscriber's status changes as described by [`EmailEventKind`].
+///
+/// Returns `false`, without changing anything, if the event was already processed.
+#[tracing::instrument(name = "Processing an email event", skip(pool, event), fields(event_id = %event.id, kind = event.kind.as_str()))]
+pub async fn process_event(pool: &PgPool, event: &EmailEvent) -> Result<bool, sqlx::Error> {
+    //NOTE: Addresses are stored normalized, see `SubscriberEmail::parse`
+    let parsed = SubscriberEmail::parse(event.email.clone()).ok();
+    let email = parsed
+        .as_ref()
+        .map(|email| email.as_ref().to_string())
+        .unwrap_or_else(|| event.email.clone());
+    let mut transaction = pool.begin().await?;
+    let inserted = sqlx::query!(
+        r#"
+    INSERT INTO email_events (
+        provider_event_id, kind, email, provider_message_id, occurred_at, received_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6)
+    ON CONFLICT (provider_event_id) DO NOTHING
+    "#,
+        event.id,
+        event.kind.as_str(),
+        email,
+        event.message_id,
+        event.occurred_at,
+        Utc::now()
+    )
+    .execute(&mut transaction)
+    .await?;
+    if inserted.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    if let (EmailEventKind::HardBounce | EmailEventKind::SoftBounce, Some(message_id)) =
+        (event.kind, &event.message_id)
+    {
+        sqlx::query!(
+            r#"
+    UPDATE issue_deliveries
+    SET bounced_at = $2
+    WHERE provider_message_id = $1 AND bounced_at IS NULL
+    "#,
+            message_id,
+            event.occurred_at
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+    let status = match event.kind {
+        EmailEventKind::HardBounce => Some(("bounced", SuppressionReason::HardBounce)),
+        EmailEventKind::SpamComplaint => Some(("complained", SuppressionReason::SpamComplaint)),
+        EmailEventKind::SoftBounce => None,
+    };
+    if let (Some((_, reason)), Some(parsed)) = (status, &parsed) {
+        let note = format!("Reported by the provider, event {}", event.id);
+        suppress(
+            &mut transaction,
+            &SuppressionEntry::address(parsed),
+            reason,
+            Some(&note),
+        )
+        .await?;
+    }
+    if let Some((status, _)) = status {
+        //NOTE: A complaint trumps a bounce, never the other way around
+        let result = sqlx::query!(
+            r#"
+    UPDATE subscriptions
+    SET status = $2, status_changed_at = now()
+    WHERE email = $1 AND status <> $2 AND status <> 'complained'
+    "#,
+            email,
+            status
+        )
+        .execute(&mut transaction)
+        .await?;
+        if result.rows_affected() > 0 {
+            tracing::info!(status, "Excluded a subscriber from future sends");
+        }
+    }
+    transaction.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_event, EmailEventKind};
+
+    fn bounce(bounce_type: &str) -> String {
+        format!(
+            r#"{{
+                "RecordType": "Bounce",
+                "ID": 4323372036854775807,
+                "Type": "{}",
+                "TypeCode": 1,
+                "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+                "Email": "john@example.com",
+                "BouncedAt": "2026-10-19T16:33:54.9070259Z",
+                "Inactive": true
+            }}"#,
+            bounce_type
+        )
+    }
+
+    #[test]
+    fn bounces_and_complaints_are_parsed() {
+        let hard = parse_event(bounce("HardBounce").as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(hard.kind, EmailEventKind::HardBounce);
+        assert_eq!(hard.id, "4323372036854775807");
+        assert_eq!(hard.email, "john@example.com");
+        assert_eq!(
+            hard.message_id.as_deref(),
+            Some("883953f4-6105-42a2-a16a-77a8eac79483")
+        );
+
+        let soft = parse_event(bounce("SoftBounce").as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(soft.kind, EmailEventKind::SoftBounce);
+
+        let complaint = parse_event(bounce("SpamComplaint").as_bytes())
+            .unwrap()
+            .unwrap();
+        assert_eq!(complaint.kind, EmailEventKind::SpamComplaint);
+    }
+
+    #[test]
+    fn events_we_do_not_act_upon_are_skipped() {
+        assert_eq!(parse_event(bounce("AutoResponder").as_bytes()), Ok(None));
+        let delivery = r#"{"RecordType": "Delivery", "ID": 1, "Recipient": "john@example.com",
+            "DeliveredAt": "2026-10-19T16:33:54Z"}"#;
+        assert_eq!(parse_event(delivery.as_bytes()), Ok(None));
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        assert!(parse_event(b"not json").is_err());
+        assert!(parse_event(br#"{"RecordType": "Bounce", "Type": "HardBounce"}"#).is_err());
+    }
+}
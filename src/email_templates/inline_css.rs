@@ -0,0 +1,263 @@
+use std::{borrow::Cow, cmp::Reverse};
+
+use lol_html::{html_content::Element, ElementContentHandlers, Selector, Settings};
+
+/// Copies the rules of the `<style>` blocks of `html` into the `style` attribute of the elements
+/// they match, for the mail clients (e.g. older Gmail and Outlook) that drop `<style>`.
+///
+/// The `<style>` blocks are left in place: at-rules such as `@media` and selectors we cannot match
+/// (e.g. `a:hover`) still work in the clients that support them. Inline styles already in the
+/// HTML take precedence over the inlined rules, and so do more specific selectors, as in a browser.
+pub fn inline_css(html: &str) -> Result<String, lol_html::errors::RewritingError> {
+    let mut rules = style_blocks(html)
+        .iter()
+        .flat_map(|css| parse_rules(css))
+        .enumerate()
+        .filter_map(|(position, rule)| {
+            let selector = rule.selector.parse::<Selector>().ok()?;
+            Some((
+                specificity(&rule.selector),
+                position,
+                selector,
+                rule.declarations,
+            ))
+        })
+        .collect::<Vec<_>>();
+    if rules.is_empty() {
+        return Ok(html.to_string());
+    }
+    //NOTE: Each handler prepends its declarations to the `style` attribute, and handlers run in
+    //registration order: registering the rule that must win first leaves its declarations last,
+    //right before the ones that were inline to begin with.
+    rules.sort_by_key(|(specificity, position, ..)| Reverse((*specificity, *position)));
+
+    let element_content_handlers = rules
+        .into_iter()
+        .map(|(_, _, selector, declarations)| {
+            (
+                Cow::Owned(selector),
+                ElementContentHandlers::default().element(move |element: &mut Element| {
+                    let style = match element.get_attribute("style") {
+                        Some(existing) => format!("{} {}", declarations, existing),
+                        None => declarations.clone(),
+                    };
+                    element.set_attribute("style", &style)?;
+                    Ok(())
+                }),
+            )
+        })
+        .collect();
+    lol_html::rewrite_str(
+        html,
+        Settings {
+            element_content_handlers,
+            ..Settings::new()
+        },
+    )
+}
+
+struct Rule {
+    selector: String,
+    /// `;`-terminated, e.g. `color: red; margin: 0;`.
+    declarations: String,
+}
+
+fn style_blocks(html: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find("<style") {
+        let Some(open_end) = rest[start..].find('>') else {
+            break;
+        };
+        let content_start = start + open_end + 1;
+        let Some(length) = rest[content_start..].find("</style>") else {
+            break;
+        };
+        blocks.push(&rest[content_start..content_start + length]);
+        rest = &rest[content_start + length..];
+    }
+    blocks
+}
+
+/// Parses the plain `selector { declarations }` rules of `css`, splitting selector lists. At-rules
+/// are skipped, along with everything nested in them.
+fn parse_rules(css: &str) -> Vec<Rule> {
+    let css = strip_comments(css);
+    let mut rules = Vec::new();
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let Some(close) = matching_brace(&rest[open..]) else {
+            break;
+        };
+        let block = &rest[open + 1..open + close];
+        rest = &rest[open + close + 1..];
+
+        if prelude.starts_with('@') {
+            continue;
+        }
+        let declarations = block
+            .split(';')
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(|d| format!("{};", d))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if declarations.is_empty() {
+            continue;
+        }
+        for selector in prelude.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            rules.push(Rule {
+                selector: selector.to_string(),
+                declarations: declarations.clone(),
+            });
+        }
+    }
+    rules
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut stripped = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// Position of the `}` closing the `{` that `s` starts with.
+fn matching_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// CSS specificity of a single selector as `(ids, classes, types)`, e.g. `(0, 1, 1)` for `p.lead`.
+fn specificity(selector: &str) -> (usize, usize, usize) {
+    let (mut ids, mut classes, mut types) = (0, 0, 0);
+    let mut chars = selector.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' => ids += 1,
+            '.' | '[' | ':' => classes += 1,
+            c if c.is_ascii_alphabetic() => {
+                types += 1;
+                while chars
+                    .next_if(|c| c.is_ascii_alphanumeric() || *c == '-')
+                    .is_some()
+                {}
+                continue;
+            }
+            _ => {}
+        }
+        //NOTE: Skip the name following `#`, `.` or `:`, and whatever is inside `[...]`
+        if c == '[' {
+            for c in chars.by_ref() {
+                if c == ']' {
+                    break;
+                }
+            }
+        } else if matches!(c, '#' | '.' | ':') {
+            while chars
+                .next_if(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+                .is_some()
+            {}
+        }
+    }
+    (ids, classes, types)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inline_css, specificity};
+
+    fn inline(style: &str, body: &str) -> String {
+        let html = format!(
+            "<html><head><style>{}</style></head><body>{}</body></html>",
+            style, body
+        );
+        let inlined = inline_css(&html).unwrap();
+        let start = inlined.find("<body>").unwrap();
+        inlined[start + "<body>".len()..inlined.find("</body>").unwrap()].to_string()
+    }
+
+    #[test]
+    fn rules_are_copied_into_the_style_attribute() {
+        assert_eq!(
+            inline("p { color: red; margin: 0 }", "<p>Hi</p>"),
+            r#"<p style="color: red; margin: 0;">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn selector_lists_and_classes_are_supported() {
+        assert_eq!(
+            inline(
+                "h1, .lead { font-weight: bold; }",
+                r#"<h1>A</h1><p class="lead">B</p><p>C</p>"#
+            ),
+            r#"<h1 style="font-weight: bold;">A</h1><p class="lead" style="font-weight: bold;">B</p><p>C</p>"#
+        );
+    }
+
+    #[test]
+    fn more_specific_rules_and_existing_inline_styles_win() {
+        //NOTE: In CSS the last declaration wins
+        assert_eq!(
+            inline(
+                ".lead { color: blue; } p { color: red; }",
+                r#"<p class="lead" style="color: green;">Hi</p>"#
+            ),
+            r#"<p class="lead" style="color: red; color: blue; color: green;">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn later_rules_win_over_earlier_ones_with_the_same_specificity() {
+        assert_eq!(
+            inline("p { color: red; } p { color: blue; }", "<p>Hi</p>"),
+            r#"<p style="color: red; color: blue;">Hi</p>"#
+        );
+    }
+
+    #[test]
+    fn at_rules_comments_and_unsupported_selectors_are_skipped() {
+        assert_eq!(
+            inline(
+                "/* p { color: red; } */ @media (max-width: 600px) { p { color: red; } } a:hover { color: red; }",
+                "<p>Hi</p><a>link</a>"
+            ),
+            "<p>Hi</p><a>link</a>"
+        );
+    }
+
+    #[test]
+    fn html_without_style_blocks_is_left_untouched() {
+        let html = "<p>Hi</p>";
+        assert_eq!(inline_css(html).unwrap(), html);
+    }
+
+    #[test]
+    fn specificity_counts_ids_classes_and_types() {
+        assert_eq!(specificity("p"), (0, 0, 1));
+        assert_eq!(specificity("div p.lead"), (0, 1, 2));
+        assert_eq!(specificity("#main .button"), (1, 1, 0));
+        assert_eq!(specificity("a[href]"), (0, 1, 1));
+    }
+}
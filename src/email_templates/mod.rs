@@ -0,0 +1,455 @@
+mod inline_css;
+
+pub use inline_css::inline_css;
+
+use std::path::Path;
+
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+
+use crate::{
+    configuration::SubscriberNameSettings,
+    domain::{SubscriberEmail, SubscriberName},
+    email_client::GMAIL_CLIPPING_THRESHOLD_BYTES,
+};
+
+/// The email templates, loaded from `email_templates.directory`.
+///
+/// Every email comes in two flavours, `<name>.html` and `<name>.txt`; anything else in the
+/// directory (layouts, partials) is only reachable through `{% extends %}` and `{% include %}`.
+/// Templates are [MiniJinja](https://docs.rs/minijinja) templates: `.html` ones escape every
+/// variable (subscriber names included) unless it is piped through `|safe`, `.txt` ones do not.
+#[derive(Debug)]
+pub struct EmailTemplates {
+    environment: Environment<'static>,
+}
+
+/// An email ready to be handed to [`EmailClient::send_email`](crate::email_client::EmailClient::send_email).
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub html: String,
+    pub text: String,
+}
+
+impl RenderedEmail {
+    /// Whether Gmail will cut the HTML part short, hiding the footer (and unsubscribe link).
+    pub fn is_clipped_by_gmail(&self) -> bool {
+        self.html.len() > GMAIL_CLIPPING_THRESHOLD_BYTES
+    }
+}
+
+/// Available to every template as `subscriber`.
+#[derive(Clone, Copy, Serialize)]
+pub struct Recipient<'a> {
+    pub name: &'a SubscriberName,
+    pub email: &'a SubscriberEmail,
+}
+
+/// Sent to new subscribers, to check that they own the address. Unlike newsletters, it has no
+/// unsubscribe link: they are not subscribed yet.
+#[derive(Serialize)]
+pub struct ConfirmationEmail<'a> {
+    pub subscriber: Recipient<'a>,
+    pub confirmation_link: &'a str,
+}
+
+/// What previews and test copies link to in place of a subscriber's own unsubscribe link.
+pub const SAMPLE_UNSUBSCRIBE_LINK: &str =
+    "https://example.com/subscriptions/unsubscribe?token=sample";
+
+#[derive(Clone, Copy, Serialize)]
+pub struct NewsletterEmail<'a> {
+    pub subscriber: Recipient<'a>,
+    /// Signed for the subscriber and the list of the issue, see
+    /// [`UnsubscribeLinks`](crate::newsletters::UnsubscribeLinks).
+    pub unsubscribe_link: &'a str,
+    pub title: &'a str,
+    /// Trusted HTML written by the editors, inserted without escaping.
+    pub html_content: &'a str,
+    /// Generated from `html_content` when empty.
+    pub text_content: &'a str,
+}
+
+impl EmailTemplates {
+    /// Loads every `.html` and `.txt` file below `directory`, named after their path relative to
+    /// it (e.g. `partials/footer.html`).
+    ///
+    /// Each required template is rendered once against sample values, so that syntax errors,
+    /// missing partials, misspelled variables and a missing confirmation or unsubscribe link stop
+    /// the application at startup rather than when the first email goes out.
+    pub fn load(directory: &Path) -> Result<Self, String> {
+        let environment = load_environment(directory, &["html", "txt"])?;
+        let templates = Self { environment };
+        templates.validate()?;
+        Ok(templates)
+    }
+
+    pub fn confirmation(
+        &self,
+        email: &ConfirmationEmail,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        self.render("confirmation", email)
+    }
+
+    pub fn newsletter(&self, email: &NewsletterEmail) -> Result<RenderedEmail, minijinja::Error> {
+        if email.text_content.trim().is_empty() {
+            let text_content = html_to_text(email.html_content);
+            return self.render(
+                "newsletter",
+                NewsletterEmail {
+                    text_content: &text_content,
+                    ..*email
+                },
+            );
+        }
+        self.render("newsletter", email)
+    }
+
+    fn render(
+        &self,
+        name: &str,
+        context: impl Serialize,
+    ) -> Result<RenderedEmail, minijinja::Error> {
+        let render = |extension: &str| {
+            self.environment
+                .get_template(&format!("{}.{}", name, extension))?
+                .render(&context)
+        };
+        let html = render("html")?;
+        //NOTE: Many clients drop `<style>` blocks: sending unstyled is better than not sending
+        let html = inline_css(&html).unwrap_or_else(|e| {
+            tracing::warn!(error.cause_chain = ?e, template = name, "Failed to inline the CSS");
+            html
+        });
+        Ok(RenderedEmail {
+            html,
+            text: render("txt")?,
+        })
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let (name, email) = sample_subscriber();
+        let invalid =
+            |template, e| format!("Email template `{}` cannot be rendered: {:#}", template, e);
+
+        //NOTE: Sample links end with a token: the HTML versions escape the `/` of a link, the query
+        //string is left alone
+        let shows = |rendered: &RenderedEmail, link: &str| {
+            let (_, token) = link
+                .split_once('?')
+                .expect("Sample links have a query string");
+            rendered.html.contains(token) && rendered.text.contains(token)
+        };
+
+        let confirmation = self
+            .confirmation(&ConfirmationEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                confirmation_link: SAMPLE_CONFIRMATION_LINK,
+            })
+            .map_err(|e| invalid("confirmation", e))?;
+        if !shows(&confirmation, SAMPLE_CONFIRMATION_LINK) {
+            return Err(
+                "Email template `confirmation` must show `confirmation_link` in both its HTML and text versions"
+                    .into(),
+            );
+        }
+
+        let newsletter = self
+            .newsletter(&NewsletterEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                unsubscribe_link: SAMPLE_UNSUBSCRIBE_LINK,
+                title: "Sample issue",
+                html_content: "<p>Sample content</p>",
+                text_content: "Sample content",
+            })
+            .map_err(|e| invalid("newsletter", e))?;
+        if !shows(&newsletter, SAMPLE_UNSUBSCRIBE_LINK) {
+            return Err(
+                "Email template `newsletter` must show `unsubscribe_link` in both its HTML and text versions"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+}
+
+const SAMPLE_CONFIRMATION_LINK: &str =
+    "https://example.com/subscriptions/confirm?subscription_token=sample";
+
+/// The subscriber templates are checked against at startup, and issues previewed for.
+pub fn sample_subscriber() -> (SubscriberName, SubscriberEmail) {
+    //NOTE: No rules: this is our own sample, not user input
+    let rules = SubscriberNameSettings {
+        max_graphemes: usize::MAX,
+        forbidden_chars: String::new(),
+        normalize_unicode: false,
+        trim_whitespace: false,
+    };
+    let name = SubscriberName::parse("Ursula Le Guin".into(), &rules)
+        .expect("The sample subscriber name is valid");
+    let email = SubscriberEmail::parse("ursula@example.com".into())
+        .expect("The sample subscriber email is valid");
+    (name, email)
+}
+
+/// A readable plain-text version of `html`, wrapped at 78 columns.
+pub fn html_to_text(html: &str) -> String {
+    html2text::from_read(html.as_bytes(), 78).unwrap_or_else(|e| {
+        tracing::warn!(error.cause_chain = ?e, "Failed to convert HTML to plain text");
+        String::new()
+    })
+}
+
+/// A MiniJinja environment holding every template below `directory` with one of `extensions`,
+/// named after their path relative to it.
+pub(crate) fn load_environment(
+    directory: &Path,
+    extensions: &[&str],
+) -> Result<Environment<'static>, String> {
+    let mut environment = Environment::new();
+    //NOTE: Referencing a variable that does not exist is an error rather than an empty string
+    environment.set_undefined_behavior(UndefinedBehavior::Strict);
+    environment.set_trim_blocks(true);
+    environment.set_keep_trailing_newline(true);
+
+    for (name, path) in template_files(directory, extensions)? {
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read `{}`: {}", path.display(), e))?;
+        environment
+            .add_template_owned(name.clone(), source)
+            .map_err(|e| format!("Invalid template `{}`: {:#}", name, e))?;
+    }
+    Ok(environment)
+}
+
+fn template_files(
+    directory: &Path,
+    extensions: &[&str],
+) -> Result<Vec<(String, std::path::PathBuf)>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![directory.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = std::fs::read_dir(&current)
+            .map_err(|e| format!("Failed to read `{}`: {}", current.display(), e))?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| extensions.contains(&e))
+            {
+                //NOTE: Template names always use `/`, whatever the platform
+                let name = path
+                    .strip_prefix(directory)
+                    .expect("The template is inside the directory")
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.push((name, path));
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use claim::assert_err;
+
+    use super::*;
+
+    fn bundled_templates() -> &'static Path {
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/email"))
+    }
+
+    /// Copies the bundled templates to a scratch directory, replacing `name` with `source`.
+    fn templates_with(name: &str, source: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("templates-{}", uuid::Uuid::new_v4()));
+        for (file, path) in template_files(bundled_templates(), &["html", "txt"]).unwrap() {
+            let target = directory.join(&file);
+            std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+            std::fs::copy(path, target).unwrap();
+        }
+        std::fs::write(directory.join(name), source).unwrap();
+        directory
+    }
+
+    fn name(s: &str) -> SubscriberName {
+        let rules = SubscriberNameSettings {
+            max_graphemes: 256,
+            forbidden_chars: "/()\"<>\\{}".into(),
+            normalize_unicode: true,
+            trim_whitespace: true,
+        };
+        SubscriberName::parse(s.into(), &rules).unwrap()
+    }
+
+    #[test]
+    fn the_bundled_templates_are_valid() {
+        EmailTemplates::load(bundled_templates()).unwrap();
+    }
+
+    #[test]
+    fn subscriber_names_are_escaped_in_html_but_not_in_text() {
+        let templates = EmailTemplates::load(bundled_templates()).unwrap();
+        let name = name("Ursula & Co");
+        let email = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let rendered = templates
+            .newsletter(&NewsletterEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                unsubscribe_link: "https://example.com/unsubscribe?token=abc",
+                title: "Issue #1",
+                html_content: "<h1>Issue #1</h1>",
+                text_content: "Issue #1",
+            })
+            .unwrap();
+
+        assert!(rendered.html.contains("Hi Ursula &amp; Co,"));
+        assert!(rendered.text.contains("Hi Ursula & Co,"));
+    }
+
+    #[test]
+    fn newsletter_content_is_inserted_as_is() {
+        let templates = EmailTemplates::load(bundled_templates()).unwrap();
+        let name = name("Ursula");
+        let email = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let rendered = templates
+            .newsletter(&NewsletterEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                unsubscribe_link: "https://example.com/unsubscribe",
+                title: "Issue #1",
+                html_content: "<h1>Issue #1</h1>",
+                text_content: "Issue #1",
+            })
+            .unwrap();
+
+        assert!(rendered.html.contains("<h1>Issue #1</h1>"));
+        //NOTE: Links are escaped too (`/` becomes `&#x2f;`), which is fine inside an attribute
+        assert!(rendered.html.contains(">Unsubscribe</a>"));
+        assert!(rendered
+            .text
+            .contains("Unsubscribe: https://example.com/unsubscribe"));
+    }
+
+    #[test]
+    fn the_layout_styles_are_inlined() {
+        let templates = EmailTemplates::load(bundled_templates()).unwrap();
+        let name = name("Ursula");
+        let email = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let rendered = templates
+            .newsletter(&NewsletterEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                unsubscribe_link: "https://example.com/unsubscribe",
+                title: "Issue #1",
+                html_content: "<h1>Issue #1</h1>",
+                text_content: "Issue #1",
+            })
+            .unwrap();
+
+        assert!(rendered
+            .html
+            .contains(r#"<p class="footer" style="color: #777777; font-size: 12px;"#));
+    }
+
+    #[test]
+    fn a_missing_text_version_is_generated_from_the_html() {
+        let templates = EmailTemplates::load(bundled_templates()).unwrap();
+        let name = name("Ursula");
+        let email = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let rendered = templates
+            .newsletter(&NewsletterEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                unsubscribe_link: "https://example.com/unsubscribe",
+                title: "Issue #1",
+                html_content: "<p>Read <a href=\"https://example.com/post\">the post</a>.</p>",
+                text_content: "",
+            })
+            .unwrap();
+
+        assert!(rendered.text.contains("Read [the post][1]."));
+        assert!(rendered.text.contains("[1]: https://example.com/post"));
+        assert!(!rendered.text.contains("<p>"));
+    }
+
+    #[test]
+    fn misspelled_variables_are_rejected_at_load_time() {
+        let directory = templates_with("newsletter.txt", "Hi {{ subscriber.nmae }}");
+        assert_err!(EmailTemplates::load(&directory));
+    }
+
+    #[test]
+    fn confirmations_show_their_link_without_an_unsubscribe_footer() {
+        let templates = EmailTemplates::load(bundled_templates()).unwrap();
+        let name = name("Ursula");
+        let email = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+
+        let rendered = templates
+            .confirmation(&ConfirmationEmail {
+                subscriber: Recipient {
+                    name: &name,
+                    email: &email,
+                },
+                confirmation_link: "https://example.com/confirm?token=abc",
+            })
+            .unwrap();
+
+        assert!(rendered
+            .text
+            .contains("https://example.com/confirm?token=abc"));
+        assert!(rendered
+            .html
+            .contains(r#"style="background-color: #2d6cdf; color: #ffffff;"#));
+        assert!(!rendered.html.contains("Unsubscribe"));
+    }
+
+    #[test]
+    fn confirmations_without_their_link_are_rejected_at_load_time() {
+        let directory = templates_with("confirmation.txt", "Hi {{ subscriber.name }}");
+        assert_err!(EmailTemplates::load(&directory));
+    }
+
+    #[test]
+    fn newsletters_without_an_unsubscribe_link_are_rejected_at_load_time() {
+        let directory = templates_with("partials/footer.txt", "Sent to {{ subscriber.email }}");
+        assert_err!(EmailTemplates::load(&directory));
+    }
+
+    #[test]
+    fn missing_partials_are_rejected_at_load_time() {
+        let directory = templates_with("newsletter.txt", r#"{% include "partials/nope.txt" %}"#);
+        assert_err!(EmailTemplates::load(&directory));
+    }
+
+    #[test]
+    fn syntax_errors_are_rejected_at_load_time() {
+        let directory = templates_with("newsletter.html", "{% if %}");
+        assert_err!(EmailTemplates::load(&directory));
+    }
+}
@@ -1,6 +1,18 @@
+pub mod anti_abuse;
+pub mod archive;
+pub mod cli;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_events;
+pub mod email_templates;
+pub mod lists;
+pub mod migrations;
+pub mod newsletters;
+pub mod rate_limit;
+pub mod reload;
 pub mod routes;
+pub mod shutdown;
 pub mod startup;
+pub mod suppressions;
 pub mod telemetry;
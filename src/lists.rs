@@ -0,0 +1,303 @@
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{domain::SubscriberEmail, email_client::Sender};
+
+/// The list subscriptions and issues go to when they do not name one.
+pub const DEFAULT_LIST_ID: &str = "default";
+
+const MAX_LIST_ID_LENGTH: usize = 64;
+const MAX_SENDER_NAME_LENGTH: usize = 100;
+
+/// One of the newsletters we run.
+#[derive(Debug, serde::Serialize)]
+pub struct MailingList {
+    /// E.g. `weekly-digest`, what `POST /subscriptions` and issues refer to the list by.
+    pub list_id: String,
+    pub name: String,
+    /// When set, issues of the list are sent from this address rather than
+    /// `email_client.sender_email`.
+    pub sender_email: Option<String>,
+    pub sender_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Subscribers the issues of the list are sent to.
+    pub active_subscribers: i64,
+}
+
+/// The editable part of a list, validated.
+#[derive(Debug)]
+pub struct ListDetails {
+    pub name: String,
+    pub sender: Option<Sender>,
+}
+
+impl ListDetails {
+    /// A sender name is only accepted along with a sender address: it would otherwise go with
+    /// whatever address the configuration holds.
+    pub fn parse(
+        name: String,
+        sender_email: Option<String>,
+        sender_name: Option<String>,
+    ) -> Result<Self, String> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            return Err("The name of the list cannot be empty".into());
+        }
+        let sender_name = sender_name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+        if let Some(sender_name) = &sender_name {
+            if sender_name.chars().count() > MAX_SENDER_NAME_LENGTH {
+                return Err(format!(
+                    "The sender name cannot be longer than {} characters",
+                    MAX_SENDER_NAME_LENGTH
+                ));
+            }
+            //NOTE: Line breaks would let the name inject headers in the email
+            if sender_name.chars().any(char::is_control) {
+                return Err("The sender name cannot contain control characters".into());
+            }
+        }
+        let sender = match (sender_email, sender_name) {
+            (Some(email), name) => Some(Sender {
+                email: SubscriberEmail::parse(email).map_err(|e| e.to_string())?,
+                name,
+            }),
+            (None, Some(_)) => return Err("`sender_name` requires `sender_email`".into()),
+            (None, None) => None,
+        };
+        Ok(Self { name, sender })
+    }
+}
+
+/// List ids are made of lowercase ASCII letters, digits and dashes, to be usable as is in URLs
+/// and forms.
+pub fn parse_list_id(list_id: &str) -> Result<&str, String> {
+    let valid = !list_id.is_empty()
+        && list_id.len() <= MAX_LIST_ID_LENGTH
+        && list_id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+    if valid {
+        Ok(list_id)
+    } else {
+        Err(format!(
+            "`{}` is not a valid list id: use up to {} lowercase letters, digits and dashes",
+            list_id, MAX_LIST_ID_LENGTH
+        ))
+    }
+}
+
+/// Returns `false` if there already is a list with this id.
+#[tracing::instrument(name = "Creating a list", skip(pool, details))]
+pub async fn create_list(
+    pool: &PgPool,
+    list_id: &str,
+    details: &ListDetails,
+) -> Result<bool, sqlx::Error> {
+    let (sender_email, sender_name) = sender_columns(details);
+    let result = sqlx::query!(
+        r#"
+    INSERT INTO lists (list_id, name, sender_email, sender_name, created_at)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (list_id) DO NOTHING
+    "#,
+        list_id,
+        details.name,
+        sender_email,
+        sender_name,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns `false` if there is no such list. Issues already queued are sent with the new sender.
+#[tracing::instrument(name = "Editing a list", skip(pool, details))]
+pub async fn update_list(
+    pool: &PgPool,
+    list_id: &str,
+    details: &ListDetails,
+) -> Result<bool, sqlx::Error> {
+    let (sender_email, sender_name) = sender_columns(details);
+    let result = sqlx::query!(
+        r#"
+    UPDATE lists
+    SET name = $2, sender_email = $3, sender_name = $4
+    WHERE list_id = $1
+    "#,
+        list_id,
+        details.name,
+        sender_email,
+        sender_name
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+fn sender_columns(details: &ListDetails) -> (Option<&str>, Option<&str>) {
+    match &details.sender {
+        Some(sender) => (Some(sender.email.as_ref()), sender.name.as_deref()),
+        None => (None, None),
+    }
+}
+
+/// Every list, oldest first.
+pub async fn list_lists(pool: &PgPool) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"
+    SELECT
+        l.list_id, l.name, l.sender_email, l.sender_name, l.created_at,
+        (
+            SELECT count(*) FROM list_subscriptions ls
+            JOIN subscriptions s ON s.id = ls.subscriber_id
+            WHERE ls.list_id = l.list_id AND ls.status = 'active' AND s.status = 'active'
+        ) AS "active_subscribers!"
+    FROM lists l
+    ORDER BY l.created_at, l.list_id
+    "#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn list_exists(pool: &PgPool, list_id: &str) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"SELECT EXISTS (SELECT 1 FROM lists WHERE list_id = $1) AS "exists!""#,
+        list_id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.exists)
+}
+
+/// Who the issues of the list are sent from, `None` for the configured sender (or if there is no
+/// such list).
+pub async fn list_sender<'e>(
+    executor: impl PgExecutor<'e>,
+    list_id: &str,
+) -> Result<Option<Sender>, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT sender_email, sender_name FROM lists WHERE list_id = $1",
+        list_id
+    )
+    .fetch_optional(executor)
+    .await?;
+    let (sender_email, name) = match row {
+        Some(row) => (row.sender_email, row.sender_name),
+        None => return Ok(None),
+    };
+    //NOTE: Checked when the list was saved, see `ListDetails::parse`
+    let sender = sender_email
+        .map(SubscriberEmail::parse)
+        .transpose()
+        .map_err(|e| sqlx::Error::Decode(e.into()))?
+        .map(|email| Sender { email, name });
+    Ok(sender)
+}
+
+/// Subscribes to the list, again if the subscriber had left it.
+pub async fn join_list(
+    transaction: &mut Transaction<'static, Postgres>,
+    subscriber_id: Uuid,
+    list_id: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO list_subscriptions (list_id, subscriber_id, status, subscribed_at)
+    VALUES ($1, $2, 'active', now())
+    ON CONFLICT (list_id, subscriber_id) DO UPDATE
+    SET status = 'active', status_changed_at = now()
+    WHERE list_subscriptions.status <> 'active'
+    "#,
+        list_id,
+        subscriber_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+/// Stops sending the issues of the list to `email`, who stays subscribed to the other lists.
+/// Returns `false` if they were not subscribed to it.
+#[tracing::instrument(name = "Leaving a list", skip(pool))]
+pub async fn leave_list(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+    list_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE list_subscriptions
+    SET status = 'unsubscribed', status_changed_at = now()
+    WHERE list_id = $2 AND status = 'active'
+        AND subscriber_id = (SELECT id FROM subscriptions WHERE email = $1)
+    "#,
+        email.as_ref(),
+        list_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Same as [`leave_list`], for the subscriber with this id: what the unsubscribe links of the issues
+/// carry.
+#[tracing::instrument(name = "Leaving a list by subscriber id", skip(pool))]
+pub async fn leave_list_by_id(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    list_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE list_subscriptions
+    SET status = 'unsubscribed', status_changed_at = now()
+    WHERE subscriber_id = $1 AND list_id = $2 AND status = 'active'
+    "#,
+        subscriber_id,
+        list_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_list_id, ListDetails};
+
+    #[test]
+    fn list_ids_are_lowercase_slugs() {
+        assert_eq!(parse_list_id("weekly-digest-2"), Ok("weekly-digest-2"));
+        assert!(parse_list_id("").is_err());
+        assert!(parse_list_id("Weekly").is_err());
+        assert!(parse_list_id("weekly digest").is_err());
+        assert!(parse_list_id(&"a".repeat(65)).is_err());
+    }
+
+    #[test]
+    fn sender_names_need_a_sender_address_and_no_line_breaks() {
+        let details = ListDetails::parse(
+            " Digest ".into(),
+            Some("digest@example.com".into()),
+            Some("The Digest".into()),
+        )
+        .unwrap();
+        assert_eq!(details.name, "Digest");
+        assert_eq!(details.sender.unwrap().name.as_deref(), Some("The Digest"));
+
+        assert!(ListDetails::parse("Digest".into(), None, Some("The Digest".into())).is_err());
+        assert!(ListDetails::parse(
+            "Digest".into(),
+            Some("digest@example.com".into()),
+            Some("The Digest\r\nBcc: everyone@example.com".into())
+        )
+        .is_err());
+        assert!(ListDetails::parse(" ".into(), None, None).is_err());
+    }
+}
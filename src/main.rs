@@ -1,10 +1,11 @@
-use std::net::TcpListener;
+use std::process::ExitCode;
 
 use actix_web::{HttpRequest, Responder};
-use secrecy::ExposeSecret;
+use clap::Parser;
 use zero2prod::{
-    configuration::get_configuration,
-    email_client::EmailClient,
+    cli::{config, migrate, newsletters, queue, send_test_email, subscribers, Cli, Command},
+    configuration::{get_configuration, Settings},
+    startup::Application,
     telemetry::{get_subscriber, init_subscriber},
 };
 
@@ -14,36 +15,51 @@ async fn _greet(req: HttpRequest) -> impl Responder {
 }
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
+async fn main() -> ExitCode {
+    //NOTE: Errors are printed with `Display`: the default `Debug` output of `io::Error` is hard to
+    //read when it carries a multi-line report
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run() -> std::io::Result<()> {
+    let command = Cli::parse().command.unwrap_or(Command::Serve);
 
-    let configuration = get_configuration().expect("Failed to read configuration");
-    let address = format!(
-        "{}:{}",
-        configuration.application.host, configuration.application.port
-    );
+    //NOTE: One-off commands print their results on stdout, logs would get in the way there
+    let log_filter = if let Command::Serve = command {
+        let (subscriber, log_filter) =
+            get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
+        init_subscriber(subscriber);
+        log_filter
+    } else {
+        let (subscriber, log_filter) =
+            get_subscriber("zero2prod".into(), "warn".into(), std::io::stderr);
+        init_subscriber(subscriber);
+        log_filter
+    };
 
-    let sender_email = configuration
-        .email_client
-        .sender()
-        .expect("Invalid sender email address");
+    match command {
+        Command::Serve => {
+            //NOTE: The application must be awaited and polled to start running. It resolves when
+            //it shuts down
+            let application = Application::build(configuration(), log_filter).await?;
+            application.run_until_stopped().await
+        }
+        Command::Migrate(command) => migrate(command, &configuration()).await,
+        Command::Subscribers(command) => subscribers(command, &configuration()).await,
+        Command::Newsletters(command) => newsletters(command, &configuration()).await,
+        Command::Queue(command) => queue(command, &configuration()).await,
+        Command::SendTestEmail(args) => send_test_email(args, &configuration()).await,
+        //NOTE: Reports problems instead of panicking on an invalid configuration
+        Command::Config(command) => config(command),
+    }
+}
 
-    let email_client = EmailClient::new(
-        configuration.email_client.base_url,
-        sender_email,
-        configuration.email_client.authorization_token,
-    );
-    //NOTE: The Server must be awaited and polled to start running. It resolves when it is shuts down
-    let listener = TcpListener::bind(address)?;
-    let pool = sqlx::PgPool::connect_lazy(
-        configuration
-            .database
-            .connection_string()
-            .expose_secret()
-            .as_str(),
-    )
-    // .await
-    .expect("Failed to connect to Postgres");
-    zero2prod::startup::run(listener, pool, email_client)?.await
+fn configuration() -> Settings {
+    get_configuration().expect("Failed to read configuration")
 }
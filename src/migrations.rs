@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migrator},
+    PgPool,
+};
+
+//NOTE: Migrations are embedded in the binary at compile time, the image does not need to ship the
+//`migrations` folder.
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Applies every pending migration.
+///
+/// `sqlx` holds a Postgres advisory lock for the whole run, so replicas booting at the same time
+/// wait for each other instead of racing on the same migration.
+#[tracing::instrument(name = "Applying pending migrations", skip(pool))]
+pub async fn run(pool: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(pool).await
+}
+
+/// Reverts the most recently applied migration, returning it (or `None` if nothing was applied).
+#[tracing::instrument(name = "Reverting the latest migration", skip(pool))]
+pub async fn revert(pool: &PgPool) -> Result<Option<MigrationStatus>, MigrateError> {
+    let status = status(pool).await?;
+    let mut applied = status
+        .into_iter()
+        .filter(|m| m.state != MigrationState::Pending);
+    let latest = match applied.next_back() {
+        Some(latest) => latest,
+        None => return Ok(None),
+    };
+    let target = applied.next_back().map(|m| m.version).unwrap_or(0);
+    MIGRATOR.undo(pool, target).await?;
+    Ok(Some(latest))
+}
+
+/// Lists every known migration, both embedded in the binary and recorded in the database, in
+/// version order.
+pub async fn status(pool: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let mut connection = pool.acquire().await?;
+    connection.ensure_migrations_table().await?;
+    let mut applied: HashMap<_, _> = connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| (m.version, m.checksum))
+        .collect();
+
+    let mut status: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            state: match applied.remove(&m.version) {
+                None => MigrationState::Pending,
+                Some(checksum) if checksum == m.checksum => MigrationState::Applied,
+                Some(_) => MigrationState::ChecksumMismatch,
+            },
+        })
+        .collect();
+    //NOTE: Whatever is left was applied by a build that knows about migrations we do not have
+    status.extend(applied.into_keys().map(|version| MigrationStatus {
+        version,
+        description: String::new(),
+        state: MigrationState::Unknown,
+    }));
+    status.sort_by_key(|m| m.version);
+    Ok(status)
+}
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MigrationState {
+    Pending,
+    Applied,
+    /// Applied, but the SQL embedded in this binary differs from what was run.
+    ChecksumMismatch,
+    /// Applied, but not embedded in this binary.
+    Unknown,
+}
+
+impl MigrationState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Applied => "applied",
+            Self::ChecksumMismatch => "checksum mismatch",
+            Self::Unknown => "unknown",
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::{borrow::Cow, sync::Arc, time::Duration};
+
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    configuration::DeliverySettings,
+    domain::{SubscriberEmail, SubscriberName},
+    email_client::{EmailClient, SendEmailError, Sender},
+    email_templates::{EmailTemplates, NewsletterEmail, Recipient, RenderedEmail},
+    lists::list_sender,
+    shutdown::ShutdownListener,
+};
+
+use super::{
+    reports::{record_delivery, DeliveryRecord},
+    DeliveryStatus, Tracker, UnsubscribeLinks,
+};
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// Sends the emails queued by [`publish_issue`](super::publish_issue), one at a time, until
+/// shutdown is requested.
+///
+/// Rows are locked with `SKIP LOCKED`, so that several instances can drain the same queue without
+/// sending any email twice.
+pub async fn run_worker_until_stopped(
+    pool: PgPool,
+    email_client: Arc<EmailClient>,
+    templates: Arc<EmailTemplates>,
+    tracker: Arc<Tracker>,
+    unsubscribe_links: Arc<UnsubscribeLinks>,
+    settings: DeliverySettings,
+    mut shutdown: ShutdownListener,
+) {
+    let poll_interval = Duration::from_millis(settings.poll_interval_milliseconds);
+    while !shutdown.is_shutdown() {
+        let outcome = try_execute_task(
+            &pool,
+            &email_client,
+            &templates,
+            &tracker,
+            &unsubscribe_links,
+            &settings,
+        )
+        .await;
+        let pause = match outcome {
+            Ok(ExecutionOutcome::TaskCompleted) => continue,
+            Ok(ExecutionOutcome::EmptyQueue) => poll_interval,
+            //NOTE: Most likely the database is unreachable, no point in hammering it
+            Err(e) => {
+                tracing::error!(error.cause_chain = ?e, "Failed to dequeue a newsletter email");
+                poll_interval.max(Duration::from_secs(1))
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(pause) => {}
+            _ = shutdown.recv() => {}
+        }
+    }
+}
+
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id = tracing::field::Empty, subscriber_email = tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    templates: &EmailTemplates,
+    tracker: &Tracker,
+    unsubscribe_links: &UnsubscribeLinks,
+    settings: &DeliverySettings,
+) -> Result<ExecutionOutcome, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let task = match dequeue_task(&mut transaction).await? {
+        Some(task) => task,
+        None => return Ok(ExecutionOutcome::EmptyQueue),
+    };
+    let span = tracing::Span::current();
+    span.record(
+        "newsletter_issue_id",
+        tracing::field::display(task.newsletter_issue_id),
+    );
+    span.record(
+        "subscriber_email",
+        tracing::field::display(&task.subscriber_email),
+    );
+
+    let attempts = task.n_retries + 1;
+    let prepared = prepare_email(
+        &mut transaction,
+        &task,
+        templates,
+        tracker,
+        unsubscribe_links,
+    )
+    .await?;
+    match prepared {
+        //NOTE: The subscriber left the list (or bounced, or complained) after the issue was
+        //published
+        PreparedOutcome::Gone => delete_task(&mut transaction, &task).await?,
+        //NOTE: Retrying would render the same issue the same way, the failure is recorded for the
+        //editors to see in the delivery report
+        PreparedOutcome::Unrenderable {
+            subscriber_id,
+            error,
+        } => {
+            let failed = DeliveryRecord {
+                newsletter_issue_id: task.newsletter_issue_id,
+                subscriber_email: &task.subscriber_email,
+                subscriber_id,
+                status: DeliveryStatus::Failed,
+                attempts,
+                provider_message_id: None,
+                error: Some(error.as_str()),
+            };
+            record_delivery(&mut transaction, &failed).await?;
+            delete_task(&mut transaction, &task).await?;
+        }
+        PreparedOutcome::Ready(PreparedEmail {
+            sender,
+            recipient,
+            subscriber_id,
+            subject,
+            email,
+        }) => {
+            let record = |status, provider_message_id, error| DeliveryRecord {
+                newsletter_issue_id: task.newsletter_issue_id,
+                subscriber_email: &task.subscriber_email,
+                subscriber_id,
+                status,
+                attempts,
+                provider_message_id,
+                error,
+            };
+            match email_client
+                .send_email_from(
+                    sender.as_ref(),
+                    &recipient,
+                    &subject,
+                    &email.html,
+                    &email.text,
+                )
+                .await
+            {
+                Ok(sent) => {
+                    let delivered =
+                        record(DeliveryStatus::Delivered, sent.message_id.as_deref(), None);
+                    record_delivery(&mut transaction, &delivered).await?;
+                    delete_task(&mut transaction, &task).await?;
+                }
+                Err(SendEmailError::Suppressed) => {
+                    tracing::info!("Skipped a newsletter email to a suppressed address");
+                    let suppressed = record(DeliveryStatus::Suppressed, None, None);
+                    record_delivery(&mut transaction, &suppressed).await?;
+                    delete_task(&mut transaction, &task).await?;
+                }
+                Err(e) if attempts >= settings.max_attempts as i32 => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        attempts,
+                        "Giving up on delivering a newsletter email"
+                    );
+                    let error = e.to_string();
+                    let failed = record(DeliveryStatus::Failed, None, Some(error.as_str()));
+                    record_delivery(&mut transaction, &failed).await?;
+                    delete_task(&mut transaction, &task).await?;
+                }
+                Err(e) => {
+                    let delay = settings.retry_delay_seconds << task.n_retries.min(16);
+                    tracing::warn!(
+                        error.cause_chain = ?e,
+                        retry_in_seconds = delay,
+                        "Failed to deliver a newsletter email, will retry"
+                    );
+                    reschedule_task(&mut transaction, &task, delay).await?;
+                }
+            }
+        }
+    }
+    transaction.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct Task {
+    newsletter_issue_id: Uuid,
+    subscriber_email: String,
+    n_retries: i32,
+}
+
+async fn dequeue_task(
+    transaction: &mut Transaction<'static, Postgres>,
+) -> Result<Option<Task>, sqlx::Error> {
+    sqlx::query_as!(
+        Task,
+        r#"
+    SELECT newsletter_issue_id, subscriber_email, n_retries
+    FROM issue_delivery_queue
+    WHERE execute_after <= now()
+    FOR UPDATE
+    SKIP LOCKED
+    LIMIT 1
+    "#
+    )
+    .fetch_optional(transaction)
+    .await
+}
+
+struct PreparedEmail {
+    /// The sender of the issue's list, if it has its own.
+    sender: Option<Sender>,
+    recipient: SubscriberEmail,
+    subscriber_id: Uuid,
+    subject: String,
+    email: RenderedEmail,
+}
+
+enum PreparedOutcome {
+    Ready(PreparedEmail),
+    /// The subscriber is gone, or left the issue's list.
+    Gone,
+    /// The templates failed on this issue, e.g. on content the startup check could not foresee.
+    Unrenderable {
+        subscriber_id: Uuid,
+        error: String,
+    },
+}
+
+/// Renders the copy of the issue meant for the subscriber. Tracked issues get their links and open pixel signed for this subscriber; the
+/// text version is left alone. Every copy links to its own unsubscribe URL.
+async fn prepare_email(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    templates: &EmailTemplates,
+    tracker: &Tracker,
+    unsubscribe_links: &UnsubscribeLinks,
+) -> Result<PreparedOutcome, sqlx::Error> {
+    let issue = sqlx::query!(
+        r#"
+    SELECT list_id, title, html_content, text_content, tracking
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1
+    "#,
+        task.newsletter_issue_id
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    let subscriber = sqlx::query!(
+        r#"
+    SELECT s.id, s.email AS "email: SubscriberEmail", s.name AS "name: SubscriberName"
+    FROM subscriptions s
+    JOIN list_subscriptions l ON l.subscriber_id = s.id
+    WHERE s.email = $1 AND s.status = 'active' AND l.list_id = $2 AND l.status = 'active'
+    "#,
+        task.subscriber_email,
+        issue.list_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let subscriber = match subscriber {
+        Some(subscriber) => subscriber,
+        None => return Ok(PreparedOutcome::Gone),
+    };
+    let html_content = if issue.tracking && tracker.is_enabled() {
+        match tracker.instrument(&issue.html_content, task.newsletter_issue_id, subscriber.id) {
+            Ok(html) => Cow::Owned(html),
+            Err(e) => {
+                tracing::warn!(error.cause_chain = ?e, "Failed to add tracking, sending as is");
+                Cow::Borrowed(issue.html_content.as_str())
+            }
+        }
+    } else {
+        Cow::Borrowed(issue.html_content.as_str())
+    };
+    let unsubscribe_link = unsubscribe_links.url(subscriber.id, &issue.list_id);
+
+    let rendered = templates.newsletter(&NewsletterEmail {
+        subscriber: Recipient {
+            name: &subscriber.name,
+            email: &subscriber.email,
+        },
+        unsubscribe_link: &unsubscribe_link,
+        title: &issue.title,
+        html_content: &html_content,
+        text_content: &issue.text_content,
+    });
+    match rendered {
+        Ok(email) => Ok(PreparedOutcome::Ready(PreparedEmail {
+            sender: list_sender(&mut *transaction, &issue.list_id).await?,
+            recipient: subscriber.email,
+            subscriber_id: subscriber.id,
+            subject: issue.title,
+            email,
+        })),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to render a newsletter email");
+            Ok(PreparedOutcome::Unrenderable {
+                subscriber_id: subscriber.id,
+                error: format!("Failed to render the email: {:#}", e),
+            })
+        }
+    }
+}
+
+async fn delete_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    DELETE FROM issue_delivery_queue
+    WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+    "#,
+        task.newsletter_issue_id,
+        task.subscriber_email
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+async fn reschedule_task(
+    transaction: &mut Transaction<'static, Postgres>,
+    task: &Task,
+    delay_seconds: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    UPDATE issue_delivery_queue
+    SET n_retries = n_retries + 1,
+        execute_after = now() + make_interval(secs => $3)
+    WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+    "#,
+        task.newsletter_issue_id,
+        task.subscriber_email,
+        delay_seconds as f64
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
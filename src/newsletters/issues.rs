@@ -0,0 +1,531 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::IssueContent;
+use crate::email_templates::{NewsletterEmail, Recipient};
+
+/// Where an issue stands: only published issues are (being) sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStatus {
+    Draft,
+    /// Published by the scheduler once `scheduled_for` is due.
+    Scheduled,
+    Published,
+}
+
+impl IssueStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Draft => "draft",
+            Self::Scheduled => "scheduled",
+            Self::Published => "published",
+        }
+    }
+}
+
+impl TryFrom<String> for IssueStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "draft" => Ok(Self::Draft),
+            "scheduled" => Ok(Self::Scheduled),
+            "published" => Ok(Self::Published),
+            other => Err(format!("{} is not a valid issue status", other)),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    /// The list the issue is sent to.
+    pub list_id: String,
+    pub title: String,
+    pub status: IssueStatus,
+    /// Set when the issue is published, see [`ArchivedIssue`].
+    pub slug: Option<String>,
+    pub markdown_content: Option<String>,
+    pub html_content: String,
+    pub text_content: String,
+    pub created_at: DateTime<Utc>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Whether opens and clicks are tracked, see [`Tracker`](super::Tracker).
+    pub tracking: bool,
+}
+
+impl NewsletterIssue {
+    /// The context of the `newsletter` templates for the copy sent to `subscriber`.
+    pub fn email<'a>(
+        &'a self,
+        subscriber: Recipient<'a>,
+        unsubscribe_link: &'a str,
+    ) -> NewsletterEmail<'a> {
+        NewsletterEmail {
+            subscriber,
+            unsubscribe_link,
+            title: &self.title,
+            html_content: &self.html_content,
+            text_content: &self.text_content,
+        }
+    }
+}
+
+/// An issue without its content, as listed to the editors.
+#[derive(Debug, serde::Serialize)]
+pub struct IssueSummary {
+    pub newsletter_issue_id: Uuid,
+    pub list_id: String,
+    pub title: String,
+    pub status: IssueStatus,
+    pub slug: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub scheduled_for: Option<DateTime<Utc>>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// A published issue, as shown in the public archive at `/archive/{slug}`.
+#[derive(Debug)]
+pub struct ArchivedIssue {
+    pub slug: String,
+    pub title: String,
+    pub html_content: String,
+    pub published_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum IssueError {
+    NotFound,
+    /// Published issues are already (being) sent: they can no longer change.
+    AlreadyPublished,
+    /// The issue is meant for a list that does not exist.
+    UnknownList,
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for IssueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "There is no such newsletter issue"),
+            Self::AlreadyPublished => write!(f, "The newsletter issue is already published"),
+            Self::UnknownList => write!(f, "There is no such list"),
+            Self::Database(_) => write!(f, "Failed to access the newsletter issues"),
+        }
+    }
+}
+
+impl std::error::Error for IssueError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Database(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for IssueError {
+    fn from(e: sqlx::Error) -> Self {
+        let constraint = e.as_database_error().and_then(|e| e.constraint());
+        match constraint {
+            Some("newsletter_issues_list_id_fkey") => Self::UnknownList,
+            _ => Self::Database(e),
+        }
+    }
+}
+
+#[tracing::instrument(name = "Saving a newsletter draft", skip(pool, content))]
+pub async fn create_draft(
+    pool: &PgPool,
+    list_id: &str,
+    title: &str,
+    content: &IssueContent,
+    tracking: bool,
+) -> Result<Uuid, IssueError> {
+    let mut transaction = pool.begin().await?;
+    let newsletter_issue_id =
+        insert_draft(&mut transaction, list_id, title, content, tracking).await?;
+    transaction.commit().await?;
+    Ok(newsletter_issue_id)
+}
+
+/// Stores a new issue and queues one email per subscriber of the list, atomically: the worker
+/// never sees an issue that is only partially queued.
+#[tracing::instrument(name = "Queueing a newsletter issue for delivery", skip(pool, content))]
+pub async fn publish_new_issue(
+    pool: &PgPool,
+    list_id: &str,
+    title: &str,
+    content: &IssueContent,
+    tracking: bool,
+) -> Result<Uuid, IssueError> {
+    let mut transaction = pool.begin().await?;
+    let newsletter_issue_id =
+        insert_draft(&mut transaction, list_id, title, content, tracking).await?;
+    publish(&mut transaction, newsletter_issue_id).await?;
+    transaction.commit().await?;
+    Ok(newsletter_issue_id)
+}
+
+pub async fn get_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<NewsletterIssue>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT
+        newsletter_issue_id, list_id, title, status, slug, markdown_content, html_content,
+        text_content, created_at, scheduled_for, published_at, tracking
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    row.map(|row| {
+        Ok(NewsletterIssue {
+            newsletter_issue_id: row.newsletter_issue_id,
+            list_id: row.list_id,
+            title: row.title,
+            status: parse_status(row.status)?,
+            slug: row.slug,
+            markdown_content: row.markdown_content,
+            html_content: row.html_content,
+            text_content: row.text_content,
+            created_at: row.created_at,
+            scheduled_for: row.scheduled_for,
+            published_at: row.published_at,
+            tracking: row.tracking,
+        })
+    })
+    .transpose()
+}
+
+/// Every issue, most recent first.
+pub async fn list_issues(pool: &PgPool) -> Result<Vec<IssueSummary>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+    SELECT
+        newsletter_issue_id, list_id, title, status, slug, created_at, scheduled_for,
+        published_at
+    FROM newsletter_issues
+    ORDER BY created_at DESC
+    "#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(IssueSummary {
+            newsletter_issue_id: row.newsletter_issue_id,
+            list_id: row.list_id,
+            title: row.title,
+            status: parse_status(row.status)?,
+            slug: row.slug,
+            created_at: row.created_at,
+            scheduled_for: row.scheduled_for,
+            published_at: row.published_at,
+        })
+    })
+    .collect()
+}
+
+/// Published issues, most recent first, up to `limit` of them when set.
+pub async fn list_archived_issues(
+    pool: &PgPool,
+    limit: Option<i64>,
+) -> Result<Vec<ArchivedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+    SELECT slug AS "slug!", title, html_content, published_at AS "published_at!"
+    FROM newsletter_issues
+    WHERE status = 'published'
+    ORDER BY published_at DESC
+    LIMIT $1
+    "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_archived_issue(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<ArchivedIssue>, sqlx::Error> {
+    sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+    SELECT slug AS "slug!", title, html_content, published_at AS "published_at!"
+    FROM newsletter_issues
+    WHERE status = 'published' AND slug = $1
+    "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Replaces the list, title, content and tracking choice of an issue that is not published yet.
+/// Scheduled issues keep their schedule.
+#[tracing::instrument(name = "Editing a newsletter issue", skip(pool, content))]
+pub async fn update_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    list_id: &str,
+    title: &str,
+    content: &IssueContent,
+    tracking: bool,
+) -> Result<(), IssueError> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET list_id = $2, title = $3, markdown_content = $4, html_content = $5, text_content = $6,
+        tracking = $7
+    WHERE newsletter_issue_id = $1 AND status <> 'published'
+    "#,
+        newsletter_issue_id,
+        list_id,
+        title,
+        content.markdown,
+        content.html,
+        content.text,
+        tracking
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(not_updated(pool, newsletter_issue_id).await);
+    }
+    Ok(())
+}
+
+/// Has the scheduler publish the issue at `send_at`, replacing any previous schedule.
+#[tracing::instrument(name = "Scheduling a newsletter issue", skip(pool))]
+pub async fn schedule_issue(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    send_at: DateTime<Utc>,
+) -> Result<(), IssueError> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'scheduled', scheduled_for = $2
+    WHERE newsletter_issue_id = $1 AND status <> 'published'
+    "#,
+        newsletter_issue_id,
+        send_at
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(not_updated(pool, newsletter_issue_id).await);
+    }
+    Ok(())
+}
+
+/// Turns a scheduled issue back into a draft. Drafts are left as they are.
+#[tracing::instrument(name = "Unscheduling a newsletter issue", skip(pool))]
+pub async fn unschedule_issue(pool: &PgPool, newsletter_issue_id: Uuid) -> Result<(), IssueError> {
+    let result = sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'draft', scheduled_for = NULL
+    WHERE newsletter_issue_id = $1 AND status <> 'published'
+    "#,
+        newsletter_issue_id
+    )
+    .execute(pool)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Err(not_updated(pool, newsletter_issue_id).await);
+    }
+    Ok(())
+}
+
+/// Publishes a draft (or scheduled issue) right away.
+#[tracing::instrument(name = "Publishing a newsletter issue", skip(pool))]
+pub async fn publish_issue(pool: &PgPool, newsletter_issue_id: Uuid) -> Result<(), IssueError> {
+    let mut transaction = pool.begin().await?;
+    if !publish(&mut transaction, newsletter_issue_id).await? {
+        return Err(not_updated(pool, newsletter_issue_id).await);
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+async fn insert_draft(
+    transaction: &mut Transaction<'static, Postgres>,
+    list_id: &str,
+    title: &str,
+    content: &IssueContent,
+    tracking: bool,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+    INSERT INTO newsletter_issues (
+        newsletter_issue_id, list_id, title, status, markdown_content, html_content,
+        text_content, created_at, tracking
+    )
+    VALUES ($1, $2, $3, 'draft', $4, $5, $6, $7, $8)
+    "#,
+        newsletter_issue_id,
+        list_id,
+        title,
+        content.markdown,
+        content.html,
+        content.text,
+        Utc::now(),
+        tracking
+    )
+    .execute(transaction)
+    .await?;
+    Ok(newsletter_issue_id)
+}
+
+/// Marks the issue as published, gives it its archive slug and queues one email per active
+/// subscriber of its list.
+/// Returns `false`, without queueing anything, if the issue does not exist or is already
+/// published.
+//NOTE: The row stays locked until the transaction ends: two callers racing to publish the same
+//issue (e.g. the scheduler on two instances) cannot both see it unpublished.
+pub(super) async fn publish(
+    transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let unpublished = sqlx::query!(
+        r#"
+    SELECT title, list_id
+    FROM newsletter_issues
+    WHERE newsletter_issue_id = $1 AND status <> 'published'
+    FOR UPDATE
+    "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    let (title, list_id) = match unpublished {
+        Some(row) => (row.title, row.list_id),
+        None => return Ok(false),
+    };
+    let slug = available_slug(transaction, &slugify(&title)).await?;
+    sqlx::query!(
+        r#"
+    UPDATE newsletter_issues
+    SET status = 'published', published_at = now(), slug = $2
+    WHERE newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id,
+        slug
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+    INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+    SELECT $1, s.email
+    FROM subscriptions s
+    JOIN list_subscriptions l ON l.subscriber_id = s.id
+    WHERE l.list_id = $2 AND l.status = 'active' AND s.status = 'active'
+    "#,
+        newsletter_issue_id,
+        list_id
+    )
+    .execute(transaction)
+    .await?;
+    Ok(true)
+}
+
+/// `base`, or `base-2`, `base-3`... when issues with the same title were published before.
+//NOTE: Two issues with the same title published at the same instant may still pick the same slug:
+//the unique constraint makes one of them fail, to be published again (by the scheduler on its next
+//run, or by hand).
+async fn available_slug(
+    transaction: &mut Transaction<'static, Postgres>,
+    base: &str,
+) -> Result<String, sqlx::Error> {
+    let taken: Vec<String> = sqlx::query!(
+        r#"
+    SELECT slug AS "slug!"
+    FROM newsletter_issues
+    WHERE slug = $1 OR slug LIKE $1 || '-%'
+    "#,
+        base
+    )
+    .fetch_all(transaction)
+    .await?
+    .into_iter()
+    .map(|row| row.slug)
+    .collect();
+    let slug = std::iter::once(base.to_string())
+        .chain((2..).map(|n| format!("{}-{}", base, n)))
+        .find(|slug| !taken.contains(slug))
+        .expect("There are infinitely many candidates");
+    Ok(slug)
+}
+
+/// Lowercase words separated by `-`, e.g. `Issue #1: Hello, World!` becomes `issue-1-hello-world`.
+/// Letters outside ASCII are kept, browsers display them as they are.
+fn slugify(title: &str) -> String {
+    let slug = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(12)
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "issue".into()
+    } else {
+        slug
+    }
+}
+
+/// Tells apart the reasons an update matched no row.
+async fn not_updated(pool: &PgPool, newsletter_issue_id: Uuid) -> IssueError {
+    match get_issue(pool, newsletter_issue_id).await {
+        Ok(Some(_)) => IssueError::AlreadyPublished,
+        Ok(None) => IssueError::NotFound,
+        Err(e) => IssueError::Database(e),
+    }
+}
+
+fn parse_status(status: String) -> Result<IssueStatus, sqlx::Error> {
+    status
+        .try_into()
+        .map_err(|e: String| sqlx::Error::Decode(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slugify, IssueStatus};
+
+    #[test]
+    fn slugs_are_lowercase_words_separated_by_dashes() {
+        assert_eq!(slugify("Issue #1: Hello, World!"), "issue-1-hello-world");
+        assert_eq!(slugify("  Über   Café "), "über-café");
+        assert_eq!(slugify("!!!"), "issue");
+    }
+
+    #[test]
+    fn statuses_round_trip_through_their_database_representation() {
+        for status in [
+            IssueStatus::Draft,
+            IssueStatus::Scheduled,
+            IssueStatus::Published,
+        ] {
+            assert_eq!(
+                IssueStatus::try_from(status.as_str().to_string()),
+                Ok(status)
+            );
+        }
+        assert!(IssueStatus::try_from("sent".to_string()).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::sync::OnceLock;
+
+use pulldown_cmark::{html, CodeBlockKind, CowStr, Event, Options, Parser, Tag, TagEnd};
+use syntect::{highlighting::ThemeSet, html::highlighted_html_for_string, parsing::SyntaxSet};
+
+/// Light theme bundled with syntect, readable on the white background of our layout.
+const CODE_THEME: &str = "InspiredGitHub";
+
+/// Link and image targets we are willing to put in an email; URLs without a scheme are relative
+/// and harmless.
+const SAFE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// Renders the Markdown written by the editors to the HTML body of an issue.
+///
+/// The output is safe to insert as-is in our templates:
+/// - raw HTML is escaped and shows up as text rather than being interpreted;
+/// - links and images pointing anywhere but `http(s)`/`mailto` URLs are reduced to their text;
+/// - fenced code blocks are highlighted with inline styles, the only styling every client keeps.
+pub fn render_markdown(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut events = Vec::new();
+    //NOTE: Links cannot be nested, a single flag is enough to drop the end of an unsafe one
+    let mut dropped_link = false;
+    let mut dropped_image = false;
+    let mut code_block: Option<(String, String)> = None;
+
+    for event in Parser::new_ext(markdown, options) {
+        if let Some((language, code)) = &mut code_block {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    events.push(Event::Html(highlight(language, code).into()));
+                    code_block = None;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Html(raw) | Event::InlineHtml(raw) => events.push(Event::Text(raw)),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .unwrap_or_default()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::Start(Tag::Link { ref dest_url, .. }) if !is_safe_url(dest_url) => {
+                dropped_link = true;
+            }
+            Event::End(TagEnd::Link) if dropped_link => dropped_link = false,
+            Event::Start(Tag::Image { ref dest_url, .. }) if !is_safe_url(dest_url) => {
+                dropped_image = true;
+            }
+            Event::End(TagEnd::Image) if dropped_image => dropped_image = false,
+            event => events.push(event),
+        }
+    }
+
+    let mut output = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut output, events.into_iter());
+    output
+}
+
+fn highlight(language: &str, code: &str) -> String {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let theme = &THEMES.get_or_init(ThemeSet::load_defaults).themes[CODE_THEME];
+
+    let syntax = syntaxes
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| syntaxes.find_syntax_plain_text());
+    highlighted_html_for_string(code, syntaxes, syntax, theme).unwrap_or_else(|e| {
+        tracing::warn!(error.cause_chain = ?e, language, "Failed to highlight a code block");
+        let mut escaped = String::from("<pre><code>");
+        html::push_html(
+            &mut escaped,
+            std::iter::once(Event::Text(CowStr::from(code))),
+        );
+        escaped.push_str("</code></pre>\n");
+        escaped
+    })
+}
+
+fn is_safe_url(url: &str) -> bool {
+    //NOTE: Browsers ignore whitespace and control characters inside the scheme, so should we
+    let url: String = url
+        .chars()
+        .filter(|c| !c.is_ascii_whitespace() && !c.is_control())
+        .collect();
+    match url.split_once(':') {
+        Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => {
+            SAFE_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_markdown;
+
+    #[test]
+    fn markdown_is_rendered_to_html() {
+        let html = render_markdown(
+            "# Issue #1\n\nHello **world**, see [the post](https://example.com/post).",
+        );
+
+        assert!(html.contains("<h1>Issue #1</h1>"));
+        assert!(html.contains("<strong>world</strong>"));
+        assert!(html.contains(r#"<a href="https://example.com/post">the post</a>"#));
+    }
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let html = render_markdown("<script>alert(1)</script>\n\nHi <img src=x onerror=alert(1)>");
+
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<img"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn links_with_unsafe_schemes_are_reduced_to_their_text() {
+        let html = render_markdown(
+            "[click](javascript:alert(1)) [me](JaVa\tScRiPt:alert(1)) ![pixel](data:image/png;base64,AAAA)",
+        );
+
+        assert!(!html.contains("<a"));
+        assert!(!html.contains("<img"));
+        assert!(!html.to_lowercase().contains("javascript"));
+        assert!(html.contains("click"));
+        assert!(html.contains("pixel"));
+    }
+
+    #[test]
+    fn relative_and_mailto_links_are_kept() {
+        let html = render_markdown("[archive](/archive) [write us](mailto:hello@example.com)");
+
+        assert!(html.contains(r#"href="/archive""#));
+        assert!(html.contains(r#"href="mailto:hello@example.com""#));
+    }
+
+    #[test]
+    fn fenced_code_blocks_are_highlighted_with_inline_styles() {
+        let html = render_markdown("```rust\nfn main() {}\n```");
+
+        assert!(html.contains("<pre style="));
+        assert!(html.contains("<span style="));
+        assert!(html.contains("main"));
+        assert!(!html.contains("class="));
+    }
+
+    #[test]
+    fn code_in_unknown_languages_is_escaped() {
+        let html = render_markdown("```klingon\n<b>Qapla'</b>\n```");
+
+        assert!(html.contains("&lt;b&gt;"));
+        assert!(!html.contains("<b>"));
+    }
+}
@@ -0,0 +1,73 @@
+mod delivery;
+mod issues;
+mod markdown;
+mod reports;
+mod scheduler;
+mod tracking;
+mod unsubscribe;
+
+pub use delivery::*;
+pub use issues::*;
+pub use markdown::render_markdown;
+pub use reports::*;
+pub use scheduler::*;
+pub use tracking::*;
+pub use unsubscribe::*;
+
+use crate::email_templates::html_to_text;
+
+/// The body of a newsletter issue, without the layout: that is applied to every copy sent.
+#[derive(Debug)]
+pub struct IssueContent {
+    /// The source the HTML and text were rendered from, kept so that the issue can be edited.
+    pub markdown: Option<String>,
+    pub html: String,
+    pub text: String,
+}
+
+impl IssueContent {
+    pub fn from_markdown(markdown: String) -> Self {
+        let html = render_markdown(&markdown);
+        let text = html_to_text(&html);
+        Self {
+            markdown: Some(markdown),
+            html,
+            text,
+        }
+    }
+
+    /// HTML written by the editors is trusted as-is; the text version is generated from it when
+    /// missing.
+    pub fn from_html(html: String, text: Option<String>) -> Self {
+        let text = text
+            .filter(|text| !text.trim().is_empty())
+            .unwrap_or_else(|| html_to_text(&html));
+        Self {
+            markdown: None,
+            html,
+            text,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IssueContent;
+
+    #[test]
+    fn markdown_content_keeps_its_source() {
+        let content = IssueContent::from_markdown("Hello *world*".into());
+
+        assert_eq!(content.markdown.as_deref(), Some("Hello *world*"));
+        assert!(content.html.contains("<em>world</em>"));
+        assert!(content.text.contains("Hello *world*"));
+    }
+
+    #[test]
+    fn a_blank_text_version_is_generated_from_the_html() {
+        let content = IssueContent::from_html("<p>Hello</p>".into(), Some("  ".into()));
+
+        assert_eq!(content.markdown, None);
+        assert_eq!(content.text.trim(), "Hello");
+    }
+}
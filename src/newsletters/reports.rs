@@ -0,0 +1,335 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use minijinja::Environment;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+/// The admin page showing a [`DeliveryReport`], bundled in the binary: unlike the emails and the
+/// archive it is not meant to be customised.
+const REPORT_TEMPLATE: &str = include_str!("report.html");
+
+/// How many recipients a page of the report lists.
+pub const REPORT_PAGE_SIZE: u32 = 100;
+
+/// What became of an email once the worker was done with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Accepted by the provider, which may still report a bounce later on.
+    Delivered,
+    /// Every attempt failed, see `delivery.max_attempts`.
+    Failed,
+    /// Not sent, the address or its domain being on the suppression list.
+    Suppressed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delivered => "delivered",
+            Self::Failed => "failed",
+            Self::Suppressed => "suppressed",
+        }
+    }
+}
+
+impl TryFrom<String> for DeliveryStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "delivered" => Ok(Self::Delivered),
+            "failed" => Ok(Self::Failed),
+            "suppressed" => Ok(Self::Suppressed),
+            other => Err(format!("{} is not a valid delivery status", other)),
+        }
+    }
+}
+
+/// How the sending of an issue went, overall and recipient by recipient.
+#[derive(Debug, serde::Serialize)]
+pub struct DeliveryReport {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub published_at: Option<DateTime<Utc>>,
+    /// Over every recipient, not only the ones of this page.
+    pub counts: DeliveryCounts,
+    /// Starting at 1.
+    pub page: u32,
+    /// `None` on the last page.
+    pub next_page: Option<u32>,
+    /// Up to [`REPORT_PAGE_SIZE`], in the order the emails were dealt with.
+    pub recipients: Vec<RecipientDelivery>,
+}
+
+#[derive(Debug, Default, PartialEq, serde::Serialize)]
+pub struct DeliveryCounts {
+    /// Emails still queued, or waiting for a retry.
+    pub pending: i64,
+    pub delivered: i64,
+    pub failed: i64,
+    pub suppressed: i64,
+    pub bounced: i64,
+    /// Recipients who loaded the open pixel, only for tracked issues.
+    pub opened: i64,
+    /// Recipients who followed at least one link, only for tracked issues.
+    pub clicked: i64,
+    /// Recipients who are no longer subscribed to the issue's list.
+    pub unsubscribed: i64,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct RecipientDelivery {
+    pub subscriber_email: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub provider_message_id: Option<String>,
+    pub error: Option<String>,
+    /// When the email was accepted by the provider, given up on or suppressed.
+    pub recorded_at: DateTime<Utc>,
+    pub bounced_at: Option<DateTime<Utc>>,
+    pub first_opened_at: Option<DateTime<Utc>>,
+    pub first_clicked_at: Option<DateTime<Utc>>,
+    pub unsubscribed: bool,
+}
+
+/// The outcome of one email, as recorded by the delivery worker.
+pub(super) struct DeliveryRecord<'a> {
+    pub newsletter_issue_id: Uuid,
+    pub subscriber_email: &'a str,
+    pub subscriber_id: Uuid,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub provider_message_id: Option<&'a str>,
+    pub error: Option<&'a str>,
+}
+
+pub(super) async fn record_delivery(
+    transaction: &mut Transaction<'static, Postgres>,
+    record: &DeliveryRecord<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO issue_deliveries (
+        newsletter_issue_id, subscriber_email, subscriber_id, status, attempts,
+        provider_message_id, error, recorded_at
+    )
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+    ON CONFLICT (newsletter_issue_id, subscriber_email) DO UPDATE
+    SET status = EXCLUDED.status, attempts = EXCLUDED.attempts,
+        provider_message_id = EXCLUDED.provider_message_id, error = EXCLUDED.error,
+        recorded_at = EXCLUDED.recorded_at
+    "#,
+        record.newsletter_issue_id,
+        record.subscriber_email,
+        record.subscriber_id,
+        record.status.as_str(),
+        record.attempts,
+        record.provider_message_id,
+        record.error,
+        Utc::now()
+    )
+    .execute(transaction)
+    .await?;
+    Ok(())
+}
+
+/// `None` if there is no such issue. `page` starts at 1.
+pub async fn delivery_report(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    page: u32,
+) -> Result<Option<DeliveryReport>, sqlx::Error> {
+    let issue = sqlx::query!(
+        "SELECT title, published_at FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let issue = match issue {
+        Some(issue) => issue,
+        None => return Ok(None),
+    };
+    let counts = delivery_counts(pool, newsletter_issue_id).await?;
+    //NOTE: One more row than a page holds tells whether there is a next page
+    let offset = i64::from(page.saturating_sub(1)) * i64::from(REPORT_PAGE_SIZE);
+    let mut recipients = sqlx::query!(
+        r#"
+    SELECT
+        d.subscriber_email, d.status, d.attempts, d.provider_message_id, d.error, d.recorded_at,
+        d.bounced_at,
+        (
+            SELECT min(occurred_at) FROM tracking_events t
+            WHERE t.newsletter_issue_id = d.newsletter_issue_id
+                AND t.subscriber_id = d.subscriber_id AND t.kind = 'open'
+        ) AS first_opened_at,
+        (
+            SELECT min(occurred_at) FROM tracking_events t
+            WHERE t.newsletter_issue_id = d.newsletter_issue_id
+                AND t.subscriber_id = d.subscriber_id AND t.kind = 'click'
+        ) AS first_clicked_at,
+        NOT EXISTS (
+            SELECT 1 FROM subscriptions s
+            JOIN list_subscriptions l ON l.subscriber_id = s.id
+            JOIN newsletter_issues i ON i.list_id = l.list_id
+            WHERE s.email = d.subscriber_email AND l.status = 'active'
+                AND i.newsletter_issue_id = d.newsletter_issue_id
+        ) AS "unsubscribed!"
+    FROM issue_deliveries d
+    WHERE d.newsletter_issue_id = $1
+    ORDER BY d.recorded_at, d.subscriber_email
+    LIMIT $2 OFFSET $3
+    "#,
+        newsletter_issue_id,
+        i64::from(REPORT_PAGE_SIZE) + 1,
+        offset
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(RecipientDelivery {
+            subscriber_email: row.subscriber_email,
+            status: row
+                .status
+                .try_into()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            attempts: row.attempts,
+            provider_message_id: row.provider_message_id,
+            error: row.error,
+            recorded_at: row.recorded_at,
+            bounced_at: row.bounced_at,
+            first_opened_at: row.first_opened_at,
+            first_clicked_at: row.first_clicked_at,
+            unsubscribed: row.unsubscribed,
+        })
+    })
+    .collect::<Result<Vec<_>, sqlx::Error>>()?;
+    let next_page = (recipients.len() > REPORT_PAGE_SIZE as usize).then_some(page + 1);
+    recipients.truncate(REPORT_PAGE_SIZE as usize);
+
+    Ok(Some(DeliveryReport {
+        newsletter_issue_id,
+        title: issue.title,
+        published_at: issue.published_at,
+        counts,
+        page,
+        next_page,
+        recipients,
+    }))
+}
+
+/// Counts every recipient of the issue in one pass.
+async fn delivery_counts(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<DeliveryCounts, sqlx::Error> {
+    //NOTE: "Unsubscribed" counts the recipients no longer subscribed to the issue's list, whatever
+    //the reason
+    sqlx::query_as!(
+        DeliveryCounts,
+        r#"
+    WITH engaged AS (
+        SELECT
+            subscriber_id,
+            bool_or(kind = 'open') AS opened,
+            bool_or(kind = 'click') AS clicked
+        FROM tracking_events
+        WHERE newsletter_issue_id = $1
+        GROUP BY subscriber_id
+    ),
+    still_subscribed AS (
+        SELECT s.email
+        FROM subscriptions s
+        JOIN list_subscriptions l ON l.subscriber_id = s.id
+        JOIN newsletter_issues i ON i.list_id = l.list_id
+        WHERE i.newsletter_issue_id = $1 AND l.status = 'active'
+    )
+    SELECT
+        (SELECT count(*) FROM issue_delivery_queue q WHERE q.newsletter_issue_id = $1)
+            AS "pending!",
+        count(*) FILTER (WHERE d.status = 'delivered') AS "delivered!",
+        count(*) FILTER (WHERE d.status = 'failed') AS "failed!",
+        count(*) FILTER (WHERE d.status = 'suppressed') AS "suppressed!",
+        count(*) FILTER (WHERE d.bounced_at IS NOT NULL) AS "bounced!",
+        count(*) FILTER (WHERE e.opened) AS "opened!",
+        count(*) FILTER (WHERE e.clicked) AS "clicked!",
+        count(*) FILTER (WHERE s.email IS NULL) AS "unsubscribed!"
+    FROM issue_deliveries d
+    LEFT JOIN engaged e ON e.subscriber_id = d.subscriber_id
+    LEFT JOIN still_subscribed s ON s.email = d.subscriber_email
+    WHERE d.newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+impl DeliveryReport {
+    /// The report as an HTML page, for the editors.
+    pub fn render_html(&self) -> Result<String, minijinja::Error> {
+        static ENVIRONMENT: OnceLock<Environment<'static>> = OnceLock::new();
+        let environment = ENVIRONMENT.get_or_init(|| {
+            let mut environment = Environment::new();
+            environment
+                .add_template("report.html", REPORT_TEMPLATE)
+                .expect("The bundled report template is valid");
+            environment
+        });
+        environment
+            .get_template("report.html")?
+            .render(minijinja::context! { report => self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use super::{DeliveryCounts, DeliveryReport, DeliveryStatus, RecipientDelivery};
+
+    fn recipient(email: &str, status: DeliveryStatus) -> RecipientDelivery {
+        RecipientDelivery {
+            subscriber_email: email.into(),
+            status,
+            attempts: 1,
+            provider_message_id: None,
+            error: None,
+            recorded_at: Utc::now(),
+            bounced_at: None,
+            first_opened_at: None,
+            first_clicked_at: None,
+            unsubscribed: false,
+        }
+    }
+
+    #[test]
+    fn the_html_report_escapes_what_it_shows() {
+        let mut failed = recipient("c@example.com", DeliveryStatus::Failed);
+        failed.error = Some("<b>rejected</b>".into());
+        let recipients = vec![failed];
+        let report = DeliveryReport {
+            newsletter_issue_id: Uuid::new_v4(),
+            title: "Issue #1 & more".into(),
+            published_at: Some(Utc::now()),
+            counts: DeliveryCounts {
+                failed: 1,
+                ..DeliveryCounts::default()
+            },
+            page: 2,
+            next_page: Some(3),
+            recipients,
+        };
+
+        let html = report.render_html().unwrap();
+
+        assert!(html.contains("Issue #1 &amp; more"));
+        assert!(html.contains("&lt;b&gt;rejected&lt;"));
+        assert!(html.contains("c@example.com"));
+        assert!(html.contains(r#"href="?format=html&amp;page=1""#));
+        assert!(html.contains(r#"href="?format=html&amp;page=3""#));
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::issues::publish;
+use crate::{configuration::DeliverySettings, shutdown::ShutdownListener};
+
+/// Publishes scheduled issues once they are due, until shutdown is requested.
+///
+/// Every instance runs its own scheduler: [`publish_due_issue`] makes sure that each issue is
+/// queued for delivery exactly once nonetheless.
+pub async fn run_scheduler_until_stopped(
+    pool: PgPool,
+    settings: DeliverySettings,
+    mut shutdown: ShutdownListener,
+) {
+    let interval = Duration::from_millis(settings.scheduler_interval_milliseconds);
+    while !shutdown.is_shutdown() {
+        loop {
+            match publish_due_issue(&pool).await {
+                Ok(Some(newsletter_issue_id)) => {
+                    tracing::info!(%newsletter_issue_id, "Published a scheduled newsletter issue");
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!(error.cause_chain = ?e, "Failed to publish scheduled issues");
+                    break;
+                }
+            }
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.recv() => {}
+        }
+    }
+}
+
+/// Publishes the scheduled issue that has been due the longest, if any, returning its id.
+//NOTE: `SKIP LOCKED` lets concurrent schedulers move on to another issue instead of waiting, and
+//`publish` refuses to publish an issue twice should one of them still get hold of the same row.
+pub async fn publish_due_issue(pool: &PgPool) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let due = sqlx::query!(
+        r#"
+    SELECT newsletter_issue_id
+    FROM newsletter_issues
+    WHERE status = 'scheduled' AND scheduled_for <= now()
+    ORDER BY scheduled_for
+    FOR UPDATE
+    SKIP LOCKED
+    LIMIT 1
+    "#
+    )
+    .fetch_optional(&mut transaction)
+    .await?;
+    let newsletter_issue_id = match due {
+        Some(row) => row.newsletter_issue_id,
+        None => return Ok(None),
+    };
+    let published = publish(&mut transaction, newsletter_issue_id).await?;
+    transaction.commit().await?;
+    Ok(published.then_some(newsletter_issue_id))
+}
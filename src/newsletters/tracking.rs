@@ -0,0 +1,413 @@
+use std::borrow::Cow;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use lol_html::{html_content::Element, ElementContentHandlers, Selector, Settings};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::configuration::TrackingSettings;
+
+const OPEN_PATH: &str = "/tracking/open";
+const CLICK_PATH: &str = "/tracking/click";
+
+/// Builds and verifies the signed URLs recording opens (a 1x1 pixel) and clicks (a redirect) on
+/// the issues that opt in to tracking.
+///
+/// Every URL carries an HMAC of what it records, and of the destination for clicks: only links we
+/// rewrote ourselves are ever followed, `/tracking/click` cannot be turned into an open redirect.
+#[derive(Clone)]
+pub struct Tracker {
+    enabled: bool,
+    base_url: String,
+    secret: Secret<String>,
+}
+
+/// The query string of the tracking URLs.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TrackingParams {
+    pub issue: Uuid,
+    pub subscriber: Uuid,
+    /// Where a click leads, missing for opens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub signature: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrackingEvent {
+    Open,
+    Click,
+}
+
+impl TrackingEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::Click => "click",
+        }
+    }
+}
+
+impl Tracker {
+    pub fn new(settings: &TrackingSettings, base_url: &str) -> Self {
+        Self {
+            enabled: settings.enabled,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            secret: settings.secret.clone(),
+        }
+    }
+
+    /// `false` when `tracking.enabled` is off: nothing is instrumented nor recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn open_url(&self, issue: Uuid, subscriber: Uuid) -> String {
+        self.url(OPEN_PATH, TrackingEvent::Open, issue, subscriber, None)
+    }
+
+    pub fn click_url(&self, issue: Uuid, subscriber: Uuid, url: &str) -> String {
+        self.url(
+            CLICK_PATH,
+            TrackingEvent::Click,
+            issue,
+            subscriber,
+            Some(url),
+        )
+    }
+
+    /// Whether `params` were signed by us for this kind of event.
+    pub fn verify(&self, event: TrackingEvent, params: &TrackingParams) -> bool {
+        let url = match (event, &params.url) {
+            (TrackingEvent::Open, None) => None,
+            (TrackingEvent::Click, Some(url)) => Some(url.as_str()),
+            _ => return false,
+        };
+        let Ok(signature) = hex::decode(&params.signature) else {
+            return false;
+        };
+        //NOTE: `verify_slice` compares in constant time
+        self.mac(event, params.issue, params.subscriber, url)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+
+    /// The HTML body of an issue as sent to `subscriber`: links to `http(s)` URLs go through
+    /// `/tracking/click`, and the open pixel is appended at the end.
+    pub fn instrument(
+        &self,
+        html: &str,
+        issue: Uuid,
+        subscriber: Uuid,
+    ) -> Result<String, lol_html::errors::RewritingError> {
+        let links = (
+            Cow::Owned(
+                "a[href]"
+                    .parse::<Selector>()
+                    .expect("The selector is valid"),
+            ),
+            ElementContentHandlers::default().element(move |element: &mut Element| {
+                let Some(href) = element.get_attribute("href") else {
+                    return Ok(());
+                };
+                //NOTE: Attribute values come with their character references still encoded
+                let href = decode_entities(href.trim());
+                if is_http_url(&href) {
+                    let tracked = self.click_url(issue, subscriber, &href);
+                    element.set_attribute("href", &tracked.replace('&', "&amp;"))?;
+                }
+                Ok(())
+            }),
+        );
+        let mut instrumented = lol_html::rewrite_str(
+            html,
+            Settings {
+                element_content_handlers: vec![links],
+                ..Settings::new()
+            },
+        )?;
+        instrumented.push_str(&format!(
+            r#"<img src="{}" width="1" height="1" alt="" style="display: block; border: 0;">"#,
+            self.open_url(issue, subscriber).replace('&', "&amp;")
+        ));
+        Ok(instrumented)
+    }
+
+    fn url(
+        &self,
+        path: &str,
+        event: TrackingEvent,
+        issue: Uuid,
+        subscriber: Uuid,
+        url: Option<&str>,
+    ) -> String {
+        let signature = self
+            .mac(event, issue, subscriber, url)
+            .finalize()
+            .into_bytes();
+        let params = TrackingParams {
+            issue,
+            subscriber,
+            url: url.map(str::to_string),
+            signature: hex::encode(signature),
+        };
+        let query =
+            serde_urlencoded::to_string(&params).expect("Tracking parameters are serializable");
+        format!("{}{}?{}", self.base_url, path, query)
+    }
+
+    fn mac(
+        &self,
+        event: TrackingEvent,
+        issue: Uuid,
+        subscriber: Uuid,
+        url: Option<&str>,
+    ) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        //NOTE: UUIDs have a fixed length, the URL is the only field that can contain anything
+        mac.update(format!("{}:{}:{}", event.as_str(), issue, subscriber).as_bytes());
+        if let Some(url) = url {
+            mac.update(b":");
+            mac.update(url.as_bytes());
+        }
+        mac
+    }
+}
+
+fn is_http_url(url: &str) -> bool {
+    let lowercase = url.to_ascii_lowercase();
+    lowercase.starts_with("http://") || lowercase.starts_with("https://")
+}
+
+/// Decodes the character references that may show up in a URL: `&amp;`, `&lt;`, `&gt;`,
+/// `&quot;`, `&apos;` and numeric ones. Anything else is left as it is.
+fn decode_entities(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let reference = rest[1..]
+            .find(';')
+            .map(|end| &rest[1..end + 1])
+            .and_then(|name| Some((name, decode_reference(name)?)));
+        match reference {
+            Some((name, c)) => {
+                decoded.push(c);
+                rest = &rest[name.len() + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_reference(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// What the tracking endpoints recorded for an issue.
+#[derive(Debug, serde::Serialize)]
+pub struct IssueStats {
+    /// Whether the issue opted in to tracking.
+    pub tracking: bool,
+    pub opens: i64,
+    /// Subscribers who opened the issue at least once.
+    pub unique_opens: i64,
+    pub clicks: i64,
+    /// Subscribers who followed at least one link.
+    pub unique_clicks: i64,
+    /// Most clicked first.
+    pub links: Vec<LinkStats>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LinkStats {
+    pub url: String,
+    pub clicks: i64,
+    pub unique_clicks: i64,
+}
+
+#[tracing::instrument(name = "Recording a tracking event", skip(pool, params), fields(issue = %params.issue))]
+pub async fn record_tracking_event(
+    pool: &PgPool,
+    event: TrackingEvent,
+    params: &TrackingParams,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+    INSERT INTO tracking_events (newsletter_issue_id, subscriber_id, kind, url, occurred_at)
+    VALUES ($1, $2, $3, $4, $5)
+    "#,
+        params.issue,
+        params.subscriber,
+        event.as_str(),
+        params.url,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// `None` if there is no such issue.
+pub async fn issue_stats(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueStats>, sqlx::Error> {
+    let issue = sqlx::query!(
+        "SELECT tracking FROM newsletter_issues WHERE newsletter_issue_id = $1",
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let tracking = match issue {
+        Some(issue) => issue.tracking,
+        None => return Ok(None),
+    };
+    let totals = sqlx::query!(
+        r#"
+    SELECT
+        COUNT(*) FILTER (WHERE kind = 'open') AS "opens!",
+        COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = 'open') AS "unique_opens!",
+        COUNT(*) FILTER (WHERE kind = 'click') AS "clicks!",
+        COUNT(DISTINCT subscriber_id) FILTER (WHERE kind = 'click') AS "unique_clicks!"
+    FROM tracking_events
+    WHERE newsletter_issue_id = $1
+    "#,
+        newsletter_issue_id
+    )
+    .fetch_one(pool)
+    .await?;
+    let links = sqlx::query_as!(
+        LinkStats,
+        r#"
+    SELECT
+        url AS "url!",
+        COUNT(*) AS "clicks!",
+        COUNT(DISTINCT subscriber_id) AS "unique_clicks!"
+    FROM tracking_events
+    WHERE newsletter_issue_id = $1 AND kind = 'click'
+    GROUP BY url
+    ORDER BY 2 DESC, url
+    "#,
+        newsletter_issue_id
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(IssueStats {
+        tracking,
+        opens: totals.opens,
+        unique_opens: totals.unique_opens,
+        clicks: totals.clicks,
+        unique_clicks: totals.unique_clicks,
+        links,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{decode_entities, Tracker, TrackingEvent, TrackingParams};
+    use crate::configuration::TrackingSettings;
+
+    fn tracker() -> Tracker {
+        let settings = TrackingSettings {
+            enabled: true,
+            secret: Secret::new("a-tracking-secret".into()),
+        };
+        Tracker::new(&settings, "https://newsletter.example.com/")
+    }
+
+    fn params(url: &str) -> TrackingParams {
+        let (_, query) = url.split_once('?').unwrap();
+        serde_urlencoded::from_str(query).unwrap()
+    }
+
+    #[test]
+    fn tracking_urls_verify_only_for_their_own_event() {
+        let tracker = tracker();
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let open = params(&tracker.open_url(issue, subscriber));
+        assert!(tracker.verify(TrackingEvent::Open, &open));
+        assert!(!tracker.verify(TrackingEvent::Click, &open));
+
+        let click = params(&tracker.click_url(issue, subscriber, "https://example.com/?a=1&b=2"));
+        assert_eq!(click.url.as_deref(), Some("https://example.com/?a=1&b=2"));
+        assert!(tracker.verify(TrackingEvent::Click, &click));
+        assert!(!tracker.verify(TrackingEvent::Open, &click));
+    }
+
+    #[test]
+    fn tampered_tracking_urls_are_rejected() {
+        let tracker = tracker();
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let mut click = params(&tracker.click_url(issue, subscriber, "https://example.com"));
+        click.url = Some("https://evil.example.com".into());
+        assert!(!tracker.verify(TrackingEvent::Click, &click));
+
+        let mut open = params(&tracker.open_url(issue, subscriber));
+        open.subscriber = Uuid::new_v4();
+        assert!(!tracker.verify(TrackingEvent::Open, &open));
+
+        open.signature = "not hex".into();
+        assert!(!tracker.verify(TrackingEvent::Open, &open));
+    }
+
+    #[test]
+    fn only_http_links_are_rewritten_and_the_pixel_is_appended() {
+        let tracker = tracker();
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = tracker
+            .instrument(
+                r#"<p><a href="https://example.com/?a=1&amp;b=2">post</a> <a href="mailto:hi@example.com">mail</a></p>"#,
+                issue,
+                subscriber,
+            )
+            .unwrap();
+
+        let expected = tracker
+            .click_url(issue, subscriber, "https://example.com/?a=1&b=2")
+            .replace('&', "&amp;");
+        assert!(html.contains(&format!(r#"<a href="{}">post</a>"#, expected)));
+        assert!(html.contains(r#"href="mailto:hi@example.com""#));
+        assert!(html.ends_with(&format!(
+            r#"<img src="{}" width="1" height="1" alt="" style="display: block; border: 0;">"#,
+            tracker.open_url(issue, subscriber).replace('&', "&amp;")
+        )));
+    }
+
+    #[test]
+    fn character_references_are_decoded() {
+        assert_eq!(
+            decode_entities("a&amp;b&#38;c&#x26;d&unknown;e&"),
+            "a&b&c&d&unknown;e&"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::configuration::UnsubscribeSettings;
+
+const UNSUBSCRIBE_PATH: &str = "/subscriptions/unsubscribe";
+
+/// Builds and verifies the unsubscribe links at the bottom of every issue.
+///
+/// A link is good for one subscriber and one list, the one the issue went to: the token holds
+/// both, followed by their HMAC so that nobody can unsubscribe someone else. Tokens do not expire,
+/// an issue read months later must still let its reader go.
+#[derive(Clone)]
+pub struct UnsubscribeLinks {
+    base_url: String,
+    secret: Secret<String>,
+}
+
+/// What a verified token unsubscribes.
+#[derive(Debug, PartialEq)]
+pub struct UnsubscribeToken {
+    pub subscriber_id: Uuid,
+    pub list_id: String,
+}
+
+impl UnsubscribeLinks {
+    pub fn new(settings: &UnsubscribeSettings, base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            secret: settings.secret.clone(),
+        }
+    }
+
+    pub fn url(&self, subscriber_id: Uuid, list_id: &str) -> String {
+        let signature = self.mac(subscriber_id, list_id).finalize().into_bytes();
+        let token = format!("{}.{}.{}", subscriber_id, list_id, hex::encode(signature));
+        let query = serde_urlencoded::to_string([("token", token)])
+            .expect("Unsubscribe tokens are serializable");
+        format!("{}{}?{}", self.base_url, UNSUBSCRIBE_PATH, query)
+    }
+
+    /// `None` unless `token` was signed by us.
+    pub fn verify(&self, token: &str) -> Option<UnsubscribeToken> {
+        //NOTE: Neither UUIDs nor list ids contain dots
+        let mut parts = token.splitn(3, '.');
+        let subscriber_id = parts.next()?.parse().ok()?;
+        let list_id = parts.next()?;
+        let signature = hex::decode(parts.next()?).ok()?;
+        //NOTE: `verify_slice` compares in constant time
+        self.mac(subscriber_id, list_id)
+            .verify_slice(&signature)
+            .ok()?;
+        Some(UnsubscribeToken {
+            subscriber_id,
+            list_id: list_id.to_string(),
+        })
+    }
+
+    fn mac(&self, subscriber_id: Uuid, list_id: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("unsubscribe:{}:{}", subscriber_id, list_id).as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{UnsubscribeLinks, UnsubscribeToken};
+    use crate::configuration::UnsubscribeSettings;
+
+    fn unsubscribe_links(secret: &str) -> UnsubscribeLinks {
+        let settings = UnsubscribeSettings {
+            secret: Secret::new(secret.into()),
+        };
+        UnsubscribeLinks::new(&settings, "https://newsletter.example.com/")
+    }
+
+    fn token(url: &str) -> String {
+        let (_, query) = url.split_once('?').unwrap();
+        let params: Vec<(String, String)> = serde_urlencoded::from_str(query).unwrap();
+        assert_eq!(params.len(), 1);
+        let (name, token) = params.into_iter().next().unwrap();
+        assert_eq!(name, "token");
+        token
+    }
+
+    #[test]
+    fn links_verify_for_their_subscriber_and_list() {
+        let links = unsubscribe_links("an-unsubscribe-secret");
+        let subscriber_id = Uuid::new_v4();
+
+        let url = links.url(subscriber_id, "weekly-digest");
+        assert!(url.starts_with("https://newsletter.example.com/subscriptions/unsubscribe?token="));
+        assert_eq!(
+            links.verify(&token(&url)),
+            Some(UnsubscribeToken {
+                subscriber_id,
+                list_id: "weekly-digest".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_rejected() {
+        let links = unsubscribe_links("an-unsubscribe-secret");
+        let subscriber_id = Uuid::new_v4();
+        let token = token(&links.url(subscriber_id, "default"));
+
+        let other_list = token.replacen(".default.", ".weekly-digest.", 1);
+        assert_eq!(links.verify(&other_list), None);
+        let other_subscriber =
+            token.replacen(&subscriber_id.to_string(), &Uuid::new_v4().to_string(), 1);
+        assert_eq!(links.verify(&other_subscriber), None);
+        assert_eq!(links.verify("not a token"), None);
+        assert_eq!(unsubscribe_links("another-secret").verify(&token), None);
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_http::h1;
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::RETRY_AFTER,
+    web, HttpResponse,
+};
+
+use super::{Decision, RateLimiter};
+use crate::configuration::TokenBucketSettings;
+
+/// Middleware limiting requests per client IP and per `email` form field, using the
+/// [`RateLimiter`] registered as application data.
+///
+/// Requests over the limit are rejected with `429 Too Many Requests` and a `Retry-After` header.
+pub struct RateLimit;
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    #[allow(clippy::type_complexity)]
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        Box::pin(async move {
+            let limiter = req
+                .app_data::<web::Data<RateLimiter>>()
+                .expect("`RateLimiter` is not registered as application data")
+                .clone();
+            let settings = limiter.settings();
+            if !settings.enabled {
+                return service.call(req).await.map(|res| res.map_into_left_body());
+            }
+
+            let ip = if settings.trust_proxy_headers {
+                req.connection_info()
+                    .realip_remote_addr()
+                    .map(|addr| addr.to_string())
+            } else {
+                req.peer_addr().map(|addr| addr.ip().to_string())
+            };
+            if let Some(ip) = ip {
+                if let Decision::Limited { retry_after } =
+                    check(&limiter, &format!("ip:{}", ip), &settings.per_ip).await
+                {
+                    return Ok(req.into_response(too_many_requests(retry_after)));
+                }
+            }
+
+            //NOTE: The body can only be read once: we put it back for the handler after peeking
+            //at the email address.
+            let body = req.extract::<web::Bytes>().await?;
+            let email = serde_urlencoded::from_bytes::<EmailField>(&body)
+                .ok()
+                .and_then(|form| form.email);
+            let (mut sender, payload) = h1::Payload::create(false);
+            sender.feed_data(body);
+            sender.feed_eof();
+            req.set_payload(payload.into());
+
+            if let Some(email) = email {
+                if let Decision::Limited { retry_after } = check(
+                    &limiter,
+                    &format!("email:{}", email.trim().to_lowercase()),
+                    &settings.per_email,
+                )
+                .await
+                {
+                    return Ok(req.into_response(too_many_requests(retry_after)));
+                }
+            }
+
+            service.call(req).await.map(|res| res.map_into_left_body())
+        })
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct EmailField {
+    email: Option<String>,
+}
+
+//NOTE: We fail open: an unavailable backend should not take the subscription form down with it.
+async fn check(limiter: &RateLimiter, key: &str, bucket: &TokenBucketSettings) -> Decision {
+    match limiter.acquire(key, bucket).await {
+        Ok(decision) => decision,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to check the rate limit");
+            Decision::Allowed
+        }
+    }
+}
+
+fn too_many_requests<B>(retry_after: std::time::Duration) -> HttpResponse<EitherBody<B>> {
+    //NOTE: `Retry-After` is in whole seconds, rounding down would invite an early retry
+    HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, retry_after.as_secs_f64().ceil() as u64))
+        .finish()
+        .map_into_right_body()
+}
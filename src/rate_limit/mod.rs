@@ -0,0 +1,322 @@
+mod middleware;
+
+pub use middleware::RateLimit;
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use sqlx::PgPool;
+
+use crate::configuration::{RateLimitBackend, RateLimitSettings, TokenBucketSettings};
+
+//NOTE: Past this many buckets the in-memory backend drops the ones that have refilled completely,
+//they behave exactly like a missing bucket. If that is not enough, the least recently used ones go
+//too, down to `IN_MEMORY_BUCKETS_AFTER_EVICTION` so that the next requests do not scan them again.
+const MAX_IN_MEMORY_BUCKETS: usize = 10_000;
+const IN_MEMORY_BUCKETS_AFTER_EVICTION: usize = 9_000;
+//NOTE: How often an instance deletes the full buckets from `rate_limit_buckets`.
+const POSTGRES_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token-bucket rate limiter for the public endpoints.
+pub struct RateLimiter {
+    backend: Backend,
+    //NOTE: Limits can be changed at runtime (see `reload`), the backend cannot.
+    settings: RwLock<RateLimitSettings>,
+}
+
+enum Backend {
+    Memory(Mutex<HashMap<String, Bucket>>),
+    Postgres {
+        pool: PgPool,
+        //NOTE: `None` until the first prune, which happens on the first request.
+        last_pruned_at: Mutex<Option<Instant>>,
+    },
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+    //NOTE: Kept per bucket since buckets of different kinds refill at different rates.
+    full_at: Instant,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+impl RateLimiter {
+    pub fn new(settings: RateLimitSettings, pool: &PgPool) -> Self {
+        let backend = match settings.backend {
+            RateLimitBackend::Memory => Backend::Memory(Mutex::new(HashMap::new())),
+            RateLimitBackend::Postgres => Backend::Postgres {
+                pool: pool.clone(),
+                last_pruned_at: Mutex::new(None),
+            },
+        };
+        Self {
+            backend,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    pub fn settings(&self) -> RateLimitSettings {
+        self.settings.read().unwrap().clone()
+    }
+
+    /// Replaces the limits applied from now on. Existing buckets are kept.
+    pub fn update_settings(&self, settings: RateLimitSettings) {
+        *self.settings.write().unwrap() = settings;
+    }
+
+    /// Takes a token from the bucket identified by `key`.
+    pub async fn acquire(
+        &self,
+        key: &str,
+        bucket: &TokenBucketSettings,
+    ) -> Result<Decision, sqlx::Error> {
+        match &self.backend {
+            Backend::Memory(buckets) => {
+                let mut buckets = buckets.lock().unwrap();
+                let now = Instant::now();
+                if !buckets.contains_key(key) && buckets.len() >= MAX_IN_MEMORY_BUCKETS {
+                    evict(&mut buckets, now);
+                }
+                let entry = buckets.entry(key.to_string()).or_insert(Bucket {
+                    tokens: bucket.capacity as f64,
+                    updated_at: now,
+                    full_at: now,
+                });
+                let (remaining, decision) = take(entry.tokens, now - entry.updated_at, bucket);
+                entry.tokens = remaining;
+                entry.updated_at = now;
+                entry.full_at = now + time_to_full(remaining, bucket);
+                Ok(decision)
+            }
+            Backend::Postgres {
+                pool,
+                last_pruned_at,
+            } => {
+                let decision = acquire_in_postgres(pool, key, bucket).await?;
+                let prune = {
+                    let mut last_pruned_at = last_pruned_at.lock().unwrap();
+                    let due =
+                        last_pruned_at.is_none_or(|at| at.elapsed() >= POSTGRES_PRUNE_INTERVAL);
+                    if due {
+                        *last_pruned_at = Some(Instant::now());
+                    }
+                    due
+                };
+                if prune {
+                    //NOTE: The token is taken already, a failure only delays the cleanup.
+                    if let Err(e) = prune_postgres_buckets(pool).await {
+                        tracing::warn!(error.cause_chain = ?e, "Failed to prune the rate limit buckets");
+                    }
+                }
+                Ok(decision)
+            }
+        }
+    }
+}
+
+/// Drops the buckets that have refilled completely, then the least recently used ones if there are
+/// still too many.
+fn evict(buckets: &mut HashMap<String, Bucket>, now: Instant) {
+    buckets.retain(|_, bucket| bucket.full_at > now);
+    if buckets.len() < MAX_IN_MEMORY_BUCKETS {
+        return;
+    }
+    let mut by_age: Vec<(Instant, String)> = buckets
+        .iter()
+        .map(|(key, bucket)| (bucket.updated_at, key.clone()))
+        .collect();
+    by_age.sort_unstable();
+    let excess = buckets.len() - IN_MEMORY_BUCKETS_AFTER_EVICTION;
+    for (_, key) in by_age.into_iter().take(excess) {
+        buckets.remove(&key);
+    }
+}
+
+#[tracing::instrument(name = "Taking a rate limit token from Postgres", skip(pool, bucket))]
+async fn acquire_in_postgres(
+    pool: &PgPool,
+    key: &str,
+    bucket: &TokenBucketSettings,
+) -> Result<Decision, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    sqlx::query!(
+        r#"
+    INSERT INTO rate_limit_buckets (key, tokens, updated_at)
+    VALUES ($1, $2, now())
+    ON CONFLICT (key) DO NOTHING
+    "#,
+        key,
+        bucket.capacity as f64
+    )
+    .execute(&mut transaction)
+    .await?;
+    //NOTE: `FOR UPDATE` serialises concurrent requests for the same key across instances, and
+    //using the database clock keeps instances with skewed clocks consistent.
+    let row = sqlx::query!(
+        r#"
+    SELECT tokens, EXTRACT(EPOCH FROM now() - updated_at)::DOUBLE PRECISION AS "elapsed_seconds!"
+    FROM rate_limit_buckets
+    WHERE key = $1
+    FOR UPDATE
+    "#,
+        key
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    let elapsed = Duration::from_secs_f64(row.elapsed_seconds.max(0.0));
+    let (remaining, decision) = take(row.tokens, elapsed, bucket);
+    sqlx::query!(
+        r#"
+    UPDATE rate_limit_buckets
+    SET tokens = $2, updated_at = now(), full_at = now() + make_interval(secs => $3)
+    WHERE key = $1
+    "#,
+        key,
+        remaining,
+        time_to_full(remaining, bucket).as_secs_f64()
+    )
+    .execute(&mut transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(decision)
+}
+
+/// Deletes the buckets that have refilled completely, returning how many there were.
+#[tracing::instrument(name = "Pruning the rate limit buckets", skip(pool))]
+async fn prune_postgres_buckets(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM rate_limit_buckets WHERE full_at <= now()")
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+fn time_to_full(tokens: f64, bucket: &TokenBucketSettings) -> Duration {
+    let missing = (bucket.capacity as f64 - tokens).max(0.0);
+    Duration::from_secs_f64(missing * bucket.refill_interval_seconds as f64)
+}
+
+fn refill(tokens: f64, elapsed: Duration, bucket: &TokenBucketSettings) -> f64 {
+    let refilled = elapsed.as_secs_f64() / bucket.refill_interval_seconds as f64;
+    (tokens + refilled).min(bucket.capacity as f64)
+}
+
+/// Refills the bucket for the time elapsed since it was last updated and tries to take a token,
+/// returning the tokens left.
+fn take(tokens: f64, elapsed: Duration, bucket: &TokenBucketSettings) -> (f64, Decision) {
+    let tokens = refill(tokens, elapsed, bucket);
+    if tokens >= 1.0 {
+        (tokens - 1.0, Decision::Allowed)
+    } else {
+        let missing = (1.0 - tokens) * bucket.refill_interval_seconds as f64;
+        (
+            tokens,
+            Decision::Limited {
+                retry_after: Duration::from_secs_f64(missing),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+    };
+
+    use super::{evict, take, Bucket, Decision, MAX_IN_MEMORY_BUCKETS};
+    use crate::configuration::TokenBucketSettings;
+
+    const BUCKET: TokenBucketSettings = TokenBucketSettings {
+        capacity: 2,
+        refill_interval_seconds: 10,
+    };
+
+    #[test]
+    fn a_full_bucket_allows_up_to_capacity_requests() {
+        let (tokens, decision) = take(2.0, Duration::ZERO, &BUCKET);
+        assert_eq!(decision, Decision::Allowed);
+        let (tokens, decision) = take(tokens, Duration::ZERO, &BUCKET);
+        assert_eq!(decision, Decision::Allowed);
+        let (_, decision) = take(tokens, Duration::ZERO, &BUCKET);
+        assert_eq!(
+            decision,
+            Decision::Limited {
+                retry_after: Duration::from_secs(10)
+            }
+        );
+    }
+
+    #[test]
+    fn retry_after_accounts_for_partial_refills() {
+        let (_, decision) = take(0.0, Duration::from_secs(4), &BUCKET);
+        assert_eq!(
+            decision,
+            Decision::Limited {
+                retry_after: Duration::from_secs(6)
+            }
+        );
+    }
+
+    #[test]
+    fn buckets_never_refill_past_capacity() {
+        let (tokens, decision) = take(0.0, Duration::from_secs(3600), &BUCKET);
+        assert_eq!(decision, Decision::Allowed);
+        assert_eq!(tokens, 1.0);
+    }
+
+    #[test]
+    fn only_buckets_that_have_refilled_are_evicted_first() {
+        let now = Instant::now();
+        let mut buckets: HashMap<String, Bucket> = (0..MAX_IN_MEMORY_BUCKETS - 1)
+            .map(|i| {
+                let bucket = Bucket {
+                    tokens: 0.0,
+                    updated_at: now - Duration::from_secs(60),
+                    //NOTE: Buckets of a slower kind, still refilling
+                    full_at: now + Duration::from_secs(3600),
+                };
+                (format!("ip:{}", i), bucket)
+            })
+            .collect();
+        buckets.insert(
+            "email:full".into(),
+            Bucket {
+                tokens: 0.0,
+                updated_at: now,
+                full_at: now,
+            },
+        );
+        evict(&mut buckets, now);
+        assert_eq!(buckets.len(), MAX_IN_MEMORY_BUCKETS - 1);
+        assert!(!buckets.contains_key("email:full"));
+    }
+
+    #[test]
+    fn the_least_recently_used_buckets_are_evicted_past_the_cap() {
+        let now = Instant::now();
+        let mut buckets: HashMap<String, Bucket> = (0..MAX_IN_MEMORY_BUCKETS as u64)
+            .map(|i| {
+                let bucket = Bucket {
+                    tokens: 0.0,
+                    updated_at: now - Duration::from_secs(MAX_IN_MEMORY_BUCKETS as u64 - i),
+                    full_at: now + Duration::from_secs(3600),
+                };
+                (format!("ip:{}", i), bucket)
+            })
+            .collect();
+        evict(&mut buckets, now);
+        assert!(buckets.len() < MAX_IN_MEMORY_BUCKETS);
+        assert!(!buckets.contains_key("ip:0"));
+        assert!(buckets.contains_key(&format!("ip:{}", MAX_IN_MEMORY_BUCKETS - 1)));
+    }
+}
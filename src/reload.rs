@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use tokio::signal::unix::{signal, SignalKind};
+
+use crate::{
+    configuration::{get_configuration, Settings},
+    email_client::EmailClient,
+    rate_limit::RateLimiter,
+    telemetry::LogFilterHandle,
+};
+
+/// Re-reads the configuration every time the process receives SIGHUP and applies the parts
+/// that can change without a restart: the log filter, the email sender/token and the rate limits.
+///
+/// A configuration that fails to load or to validate is rejected as a whole, the process keeps
+/// running with the previous one.
+pub async fn reload_on_sighup(
+    mut current: Settings,
+    email_client: Arc<EmailClient>,
+    rate_limiter: Arc<RateLimiter>,
+    log_filter: LogFilterHandle,
+) -> Result<(), std::io::Error> {
+    let mut hangup = signal(SignalKind::hangup())?;
+    while hangup.recv().await.is_some() {
+        tracing::info!("Received SIGHUP, reloading the configuration");
+        match get_configuration() {
+            Ok(new) => current = apply(&current, new, &email_client, &rate_limiter, &log_filter),
+            Err(e) => tracing::error!(
+                error.message = %e,
+                "Rejected the new configuration, keeping the current one"
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// Applies the reloadable parts of `new` and returns the settings now in effect.
+fn apply(
+    current: &Settings,
+    new: Settings,
+    email_client: &EmailClient,
+    rate_limiter: &RateLimiter,
+    log_filter: &LogFilterHandle,
+) -> Settings {
+    let changes = current.diff(&new);
+    if changes.is_empty() {
+        tracing::info!("The configuration did not change");
+        return current.clone();
+    }
+    for change in &changes {
+        if change.reloadable {
+            tracing::info!(change = %change, "Applying configuration change");
+        } else {
+            tracing::warn!(change = %change, "Configuration change requires a restart to take effect");
+        }
+    }
+
+    //NOTE: Non-reloadable settings keep reflecting what the process is actually running with,
+    //so that they keep being reported until the next restart.
+    let mut effective = current.clone();
+
+    if new.application.log_filter != current.application.log_filter {
+        //NOTE: Removing `log_filter` keeps the current filter: the one chosen at startup (from
+        //`RUST_LOG` or the default level) is gone once it has been replaced.
+        if let Some(directives) = &new.application.log_filter {
+            if let Err(e) = log_filter.set(directives) {
+                tracing::error!(error.message = %e, "Failed to apply the new log filter");
+            }
+        }
+        effective.application.log_filter = new.application.log_filter;
+    }
+
+    if changes
+        .iter()
+        .any(|change| change.name.starts_with("email_client.") && change.reloadable)
+    {
+        //NOTE: `get_configuration` already validated the sender
+        match new.email_client.sender() {
+            Ok(sender) => {
+                email_client
+                    .update_credentials(sender, new.email_client.authorization_token.clone());
+                effective.email_client.sender_email = new.email_client.sender_email;
+                effective.email_client.authorization_token = new.email_client.authorization_token;
+            }
+            Err(e) => tracing::error!(error.message = %e, "Failed to apply the new email sender"),
+        }
+    }
+
+    if changes
+        .iter()
+        .any(|change| change.name.starts_with("rate_limit.") && change.reloadable)
+    {
+        let mut rate_limit = new.rate_limit;
+        rate_limit.backend = current.rate_limit.backend;
+        rate_limiter.update_settings(rate_limit.clone());
+        effective.rate_limit = rate_limit;
+    }
+
+    effective
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::*;
+    use crate::{
+        configuration::tests::settings, domain::SubscriberEmail, startup::get_connection_pool,
+        telemetry::get_subscriber,
+    };
+
+    #[tokio::test]
+    async fn reloadable_settings_are_applied_and_the_rest_is_kept() {
+        let current = settings();
+        let email_client = EmailClient::new(
+            current.email_client.base_url.clone(),
+            SubscriberEmail::parse(current.email_client.sender_email.clone()).unwrap(),
+            Secret::new("token".into()),
+        );
+        let rate_limiter = RateLimiter::new(
+            current.rate_limit.clone(),
+            &get_connection_pool(&current.database),
+        );
+        let (_subscriber, log_filter) = get_subscriber("test".into(), "info".into(), std::io::sink);
+
+        let mut new = current.clone();
+        new.application.port += 1;
+        new.application.log_filter = Some("warn".into());
+        new.email_client.sender_email = "reloaded@example.com".into();
+        new.rate_limit.per_ip.capacity += 1;
+
+        let effective = apply(&current, new, &email_client, &rate_limiter, &log_filter);
+
+        assert_eq!(effective.application.port, current.application.port);
+        assert_eq!(effective.application.log_filter.as_deref(), Some("warn"));
+        assert_eq!(effective.email_client.sender_email, "reloaded@example.com");
+        assert_eq!(log_filter.current().unwrap(), "warn");
+        assert_eq!(
+            rate_limiter.settings().per_ip.capacity,
+            current.rate_limit.per_ip.capacity + 1
+        );
+    }
+}
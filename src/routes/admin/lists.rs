@@ -0,0 +1,82 @@
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use super::Admin;
+use crate::lists::{create_list, list_lists, parse_list_id, update_list, ListDetails};
+
+/// `sender_email` and `sender_name` are both optional: without them, the issues of the list are
+/// sent from `email_client.sender_email`.
+#[derive(serde::Deserialize)]
+pub struct ListForm {
+    name: String,
+    #[serde(default)]
+    sender_email: Option<String>,
+    #[serde(default)]
+    sender_name: Option<String>,
+}
+
+impl ListForm {
+    fn parse(self) -> Result<ListDetails, HttpResponse> {
+        ListDetails::parse(self.name, self.sender_email, self.sender_name)
+            .map_err(|e| HttpResponse::BadRequest().body(e))
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewListForm {
+    list_id: String,
+    #[serde(flatten)]
+    details: ListForm,
+}
+
+pub async fn get_lists(_admin: Admin, pool: web::Data<PgPool>) -> impl Responder {
+    match list_lists(&pool).await {
+        Ok(lists) => HttpResponse::Ok().json(lists),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Answers `409 Conflict` if there already is a list with this id.
+pub async fn add_list(
+    _admin: Admin,
+    form: web::Json<NewListForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let NewListForm { list_id, details } = form.into_inner();
+    let list_id = match parse_list_id(&list_id) {
+        Ok(list_id) => list_id,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    let details = match details.parse() {
+        Ok(details) => details,
+        Err(response) => return response,
+    };
+    match create_list(&pool, list_id, &details).await {
+        Ok(true) => HttpResponse::Created().finish(),
+        Ok(false) => HttpResponse::Conflict().body(format!("The list {} already exists", list_id)),
+        Err(e) => internal_error(e),
+    }
+}
+
+/// Replaces the name and sender of a list.
+pub async fn edit_list(
+    _admin: Admin,
+    list_id: web::Path<String>,
+    form: web::Json<ListForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let details = match form.into_inner().parse() {
+        Ok(details) => details,
+        Err(response) => return response,
+    };
+    match update_list(&pool, &list_id, &details).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => internal_error(e),
+    }
+}
+
+fn internal_error(e: sqlx::Error) -> HttpResponse {
+    tracing::error!(error.cause_chain = ?e, "Failed to access the lists");
+    HttpResponse::InternalServerError().finish()
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use actix_web::{web, HttpResponse, Responder};
+
+use super::Admin;
+use crate::telemetry::LogFilterHandle;
+
+#[derive(serde::Serialize)]
+struct LogFilterResponse {
+    log_filter: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LogFilterUpdate {
+    log_filter: String,
+    /// When set, the previous filter is restored after this many seconds.
+    ttl_seconds: Option<u64>,
+}
+
+pub async fn get_log_filter(_admin: Admin, handle: web::Data<LogFilterHandle>) -> impl Responder {
+    match handle.current() {
+        Ok(log_filter) => HttpResponse::Ok().json(LogFilterResponse { log_filter }),
+        Err(_) => HttpResponse::InternalServerError().finish(),
+    }
+}
+
+#[tracing::instrument(
+    name = "Changing the log filter",
+    skip(_admin, update, handle),
+    fields(
+        log_filter = %update.log_filter,
+        ttl_seconds = ?update.ttl_seconds
+    )
+)]
+pub async fn update_log_filter(
+    _admin: Admin,
+    update: web::Json<LogFilterUpdate>,
+    handle: web::Data<LogFilterHandle>,
+) -> impl Responder {
+    let result = match update.ttl_seconds {
+        Some(ttl) => handle.set_temporarily(&update.log_filter, Duration::from_secs(ttl)),
+        None => handle.set(&update.log_filter),
+    };
+    match result {
+        Ok(()) => HttpResponse::Ok().json(LogFilterResponse {
+            log_filter: update.0.log_filter,
+        }),
+        Err(error) => HttpResponse::BadRequest().body(error),
+    }
+}
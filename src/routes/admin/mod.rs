@@ -0,0 +1,61 @@
+mod lists;
+mod log_filter;
+mod newsletters;
+mod suppressions;
+
+pub use lists::*;
+pub use log_filter::*;
+pub use newsletters::*;
+pub use suppressions::*;
+
+use std::future::{ready, Ready};
+
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, HttpResponse};
+use secrecy::{ExposeSecret, Secret};
+
+/// Bearer token granting access to the `/admin` endpoints.
+pub struct AdminToken(pub Secret<String>);
+
+/// Extractor guarding admin endpoints: the request is rejected with `401 Unauthorized` unless it
+/// carries `Authorization: Bearer <application.admin_token>`.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(authenticate(req))
+    }
+}
+
+fn authenticate(req: &HttpRequest) -> Result<Admin, actix_web::Error> {
+    let expected = req
+        .app_data::<web::Data<AdminToken>>()
+        .expect("`AdminToken` is not registered as application data");
+    let provided = req
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token, expected.0.expose_secret()) => Ok(Admin),
+        _ => {
+            let response = HttpResponse::Unauthorized()
+                .insert_header(("WWW-Authenticate", "Bearer"))
+                .finish();
+            Err(InternalError::from_response("Invalid admin token", response).into())
+        }
+    }
+}
+
+//NOTE: Comparing byte by byte without short-circuiting, so that the response time does not leak
+//how much of the token was guessed right.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
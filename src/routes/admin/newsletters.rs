@@ -0,0 +1,408 @@
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use super::Admin;
+use crate::{
+    configuration::SubscriberNameSettings,
+    domain::{SubscriberEmail, UnvalidatedSubscriberName},
+    email_client::{EmailClient, SendEmailError},
+    email_templates::{sample_subscriber, EmailTemplates, Recipient, SAMPLE_UNSUBSCRIBE_LINK},
+    lists::{list_sender, DEFAULT_LIST_ID},
+    newsletters::{
+        create_draft, delivery_report, get_issue, issue_stats, list_issues, publish_issue,
+        publish_new_issue, schedule_issue, unschedule_issue, update_issue, IssueContent,
+        IssueError,
+    },
+};
+
+/// Either `{"title", "markdown"}` or `{"title", "html", "text"}`, `text` being optional.
+/// `"tracking": true` opts the issue in to open and click tracking, `"list"` picks the list it is
+/// sent to (the default list otherwise).
+#[derive(serde::Deserialize)]
+pub struct NewsletterForm {
+    title: String,
+    #[serde(flatten)]
+    body: NewsletterBody,
+    #[serde(default)]
+    tracking: bool,
+    #[serde(default)]
+    list: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum NewsletterBody {
+    Markdown {
+        markdown: String,
+    },
+    Html {
+        html: String,
+        #[serde(default)]
+        text: Option<String>,
+    },
+}
+
+struct Newsletter {
+    list_id: String,
+    title: String,
+    content: IssueContent,
+    tracking: bool,
+}
+
+impl NewsletterForm {
+    fn parse(self) -> Result<Newsletter, HttpResponse> {
+        if self.title.trim().is_empty() {
+            return Err(HttpResponse::BadRequest().body("The title cannot be empty"));
+        }
+        let content = match self.body {
+            NewsletterBody::Markdown { markdown } => IssueContent::from_markdown(markdown),
+            NewsletterBody::Html { html, text } => IssueContent::from_html(html, text),
+        };
+        Ok(Newsletter {
+            list_id: self.list.unwrap_or_else(|| DEFAULT_LIST_ID.to_string()),
+            title: self.title,
+            content,
+            tracking: self.tracking,
+        })
+    }
+}
+
+#[derive(serde::Serialize)]
+struct CreatedNewsletter {
+    newsletter_issue_id: Uuid,
+}
+
+/// Publishes an issue to every subscriber of its list. Emails are sent in the background, the
+/// response only confirms they are queued.
+#[tracing::instrument(
+    name = "Publishing a newsletter issue",
+    skip(_admin, newsletter, pool),
+    fields(title = %newsletter.title)
+)]
+pub async fn publish_newsletter(
+    _admin: Admin,
+    newsletter: web::Json<NewsletterForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let newsletter = match newsletter.into_inner().parse() {
+        Ok(newsletter) => newsletter,
+        Err(response) => return response,
+    };
+    let published = publish_new_issue(
+        &pool,
+        &newsletter.list_id,
+        &newsletter.title,
+        &newsletter.content,
+        newsletter.tracking,
+    )
+    .await;
+    match published {
+        Ok(newsletter_issue_id) => HttpResponse::Accepted().json(CreatedNewsletter {
+            newsletter_issue_id,
+        }),
+        Err(e) => issue_error(e),
+    }
+}
+
+/// Saves an issue without sending it, see [`publish_draft`] and [`schedule_newsletter`].
+pub async fn create_newsletter_draft(
+    _admin: Admin,
+    newsletter: web::Json<NewsletterForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let newsletter = match newsletter.into_inner().parse() {
+        Ok(newsletter) => newsletter,
+        Err(response) => return response,
+    };
+    let created = create_draft(
+        &pool,
+        &newsletter.list_id,
+        &newsletter.title,
+        &newsletter.content,
+        newsletter.tracking,
+    )
+    .await;
+    match created {
+        Ok(newsletter_issue_id) => HttpResponse::Created().json(CreatedNewsletter {
+            newsletter_issue_id,
+        }),
+        Err(e) => issue_error(e),
+    }
+}
+
+pub async fn list_newsletters(_admin: Admin, pool: web::Data<PgPool>) -> impl Responder {
+    match list_issues(&pool).await {
+        Ok(issues) => HttpResponse::Ok().json(issues),
+        Err(e) => internal_error(IssueError::from(e)),
+    }
+}
+
+/// The issue as stored, Markdown source included, for the editors to pick up where they left.
+pub async fn get_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match get_issue(&pool, *newsletter_issue_id).await {
+        Ok(Some(issue)) => HttpResponse::Ok().json(issue),
+        Ok(None) => issue_error(IssueError::NotFound),
+        Err(e) => issue_error(e.into()),
+    }
+}
+
+pub async fn update_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    newsletter: web::Json<NewsletterForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let newsletter = match newsletter.into_inner().parse() {
+        Ok(newsletter) => newsletter,
+        Err(response) => return response,
+    };
+    let updated = update_issue(
+        &pool,
+        *newsletter_issue_id,
+        &newsletter.list_id,
+        &newsletter.title,
+        &newsletter.content,
+        newsletter.tracking,
+    )
+    .await;
+    match updated {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => issue_error(e),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreviewQuery {
+    /// `html` (the default) or `text`.
+    #[serde(default)]
+    format: Option<String>,
+}
+
+/// The issue as a subscriber would receive it, rendered for a sample subscriber (and unsubscribe
+/// link).
+pub async fn preview_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    query: web::Query<PreviewQuery>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<EmailTemplates>,
+) -> impl Responder {
+    let issue = match get_issue(&pool, *newsletter_issue_id).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return issue_error(IssueError::NotFound),
+        Err(e) => return issue_error(e.into()),
+    };
+    let (name, email) = sample_subscriber();
+    let recipient = Recipient {
+        name: &name,
+        email: &email,
+    };
+    let rendered = match templates.newsletter(&issue.email(recipient, SAMPLE_UNSUBSCRIBE_LINK)) {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to render a newsletter preview");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    match query.format.as_deref() {
+        None | Some("html") => HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(rendered.html),
+        Some("text") => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body(rendered.text),
+        Some(_) => HttpResponse::BadRequest().body("`format` must be either `html` or `text`"),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestSend {
+    email: SubscriberEmail,
+    /// Greets the recipient by this name rather than the sample subscriber's.
+    #[serde(default)]
+    name: Option<UnvalidatedSubscriberName>,
+}
+
+/// Sends a single copy of the issue, whatever its status, to check how it looks in a real inbox.
+/// It comes from the sender of the issue's list, with a sample unsubscribe link: the recipient
+/// does not have to be a subscriber.
+#[tracing::instrument(
+    name = "Sending a test copy of a newsletter issue",
+    skip(_admin, test_send, pool, templates, email_client, name_rules),
+    fields(recipient = %test_send.email)
+)]
+pub async fn send_test_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    test_send: web::Json<TestSend>,
+    pool: web::Data<PgPool>,
+    templates: web::Data<EmailTemplates>,
+    email_client: web::Data<EmailClient>,
+    name_rules: web::Data<SubscriberNameSettings>,
+) -> impl Responder {
+    let TestSend { email, name } = test_send.into_inner();
+    let name = match name.map(|name| name.validate(&name_rules)) {
+        Some(Ok(name)) => name,
+        Some(Err(e)) => return HttpResponse::BadRequest().body(e.to_string()),
+        None => sample_subscriber().0,
+    };
+    let issue = match get_issue(&pool, *newsletter_issue_id).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return issue_error(IssueError::NotFound),
+        Err(e) => return issue_error(e.into()),
+    };
+    let recipient = Recipient {
+        name: &name,
+        email: &email,
+    };
+    let rendered = match templates.newsletter(&issue.email(recipient, SAMPLE_UNSUBSCRIBE_LINK)) {
+        Ok(rendered) => rendered,
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to render a newsletter test copy");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let sender = match list_sender(pool.get_ref(), &issue.list_id).await {
+        Ok(sender) => sender,
+        Err(e) => return issue_error(e.into()),
+    };
+    let subject = format!("[Test] {}", issue.title);
+    match email_client
+        .send_email_from(
+            sender.as_ref(),
+            &email,
+            &subject,
+            &rendered.html,
+            &rendered.text,
+        )
+        .await
+    {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(e @ SendEmailError::Suppressed) => HttpResponse::Conflict().body(e.to_string()),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to send a newsletter test copy");
+            HttpResponse::BadGateway().finish()
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct Schedule {
+    /// RFC 3339, e.g. `2026-10-20T08:00:00Z`.
+    send_at: DateTime<Utc>,
+}
+
+pub async fn schedule_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    schedule: web::Json<Schedule>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    if schedule.send_at <= Utc::now() {
+        return HttpResponse::BadRequest().body("`send_at` must be in the future");
+    }
+    match schedule_issue(&pool, *newsletter_issue_id, schedule.send_at).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => issue_error(e),
+    }
+}
+
+/// Turns a scheduled issue back into a draft.
+pub async fn unschedule_newsletter(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match unschedule_issue(&pool, *newsletter_issue_id).await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(e) => issue_error(e),
+    }
+}
+
+/// Publishes a draft or scheduled issue right away.
+pub async fn publish_draft(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match publish_issue(&pool, *newsletter_issue_id).await {
+        Ok(()) => HttpResponse::Accepted().finish(),
+        Err(e) => issue_error(e),
+    }
+}
+
+/// Opens and clicks recorded so far, overall and per link.
+pub async fn newsletter_stats(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    match issue_stats(&pool, *newsletter_issue_id).await {
+        Ok(Some(stats)) => HttpResponse::Ok().json(stats),
+        Ok(None) => issue_error(IssueError::NotFound),
+        Err(e) => issue_error(e.into()),
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ReportQuery {
+    /// `json` (the default) or `html`.
+    #[serde(default)]
+    format: Option<String>,
+    /// Of the recipient log, starting at 1 (the default).
+    #[serde(default)]
+    page: Option<u32>,
+}
+
+/// How the sending of an issue went: counts per outcome, and the log of every email with the id
+/// the provider gave it, `REPORT_PAGE_SIZE` recipients at a time.
+pub async fn newsletter_report(
+    _admin: Admin,
+    newsletter_issue_id: web::Path<Uuid>,
+    query: web::Query<ReportQuery>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let page = match query.page {
+        None => 1,
+        Some(0) => return HttpResponse::BadRequest().body("`page` starts at 1"),
+        Some(page) => page,
+    };
+    let report = match delivery_report(&pool, *newsletter_issue_id, page).await {
+        Ok(Some(report)) => report,
+        Ok(None) => return issue_error(IssueError::NotFound),
+        Err(e) => return issue_error(e.into()),
+    };
+    match query.format.as_deref() {
+        None | Some("json") => HttpResponse::Ok().json(report),
+        Some("html") => match report.render_html() {
+            Ok(page) => HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body(page),
+            Err(e) => {
+                tracing::error!(error.cause_chain = ?e, "Failed to render a delivery report");
+                HttpResponse::InternalServerError().finish()
+            }
+        },
+        Some(_) => HttpResponse::BadRequest().body("`format` must be either `json` or `html`"),
+    }
+}
+
+fn issue_error(e: IssueError) -> HttpResponse {
+    match e {
+        IssueError::NotFound => HttpResponse::NotFound().body(e.to_string()),
+        IssueError::AlreadyPublished => HttpResponse::Conflict().body(e.to_string()),
+        IssueError::UnknownList => HttpResponse::BadRequest().body(e.to_string()),
+        IssueError::Database(_) => internal_error(e),
+    }
+}
+
+fn internal_error(e: IssueError) -> HttpResponse {
+    tracing::error!(error.cause_chain = ?e, "Failed to access the newsletter issues");
+    HttpResponse::InternalServerError().finish()
+}
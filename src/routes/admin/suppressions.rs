@@ -0,0 +1,73 @@
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use super::Admin;
+use crate::suppressions::{
+    list_suppressions, suppress, unsuppress, SuppressionEntry, SuppressionReason,
+};
+
+#[derive(serde::Deserialize)]
+pub struct SuppressionForm {
+    /// `user@example.com`, or `example.com` to suppress a whole domain.
+    entry: String,
+    note: Option<String>,
+}
+
+pub async fn get_suppressions(_admin: Admin, pool: web::Data<PgPool>) -> impl Responder {
+    match list_suppressions(&pool).await {
+        Ok(suppressions) => HttpResponse::Ok().json(suppressions),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to list the suppressions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Answers `201 Created`, or `200 OK` if the entry was already on the list.
+#[tracing::instrument(name = "Suppressing an entry", skip(_admin, form, pool), fields(entry = %form.entry))]
+pub async fn add_suppression(
+    _admin: Admin,
+    form: web::Json<SuppressionForm>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let entry = match SuppressionEntry::parse(&form.entry) {
+        Ok(entry) => entry,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match suppress(
+        pool.get_ref(),
+        &entry,
+        SuppressionReason::Manual,
+        form.note.as_deref(),
+    )
+    .await
+    {
+        Ok(true) => HttpResponse::Created().finish(),
+        Ok(false) => HttpResponse::Ok().finish(),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to suppress an entry");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// Takes the entry as listed, domains with or without their leading `@`.
+#[tracing::instrument(name = "Unsuppressing an entry", skip(_admin, pool))]
+pub async fn remove_suppression(
+    _admin: Admin,
+    entry: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> impl Responder {
+    let entry = match SuppressionEntry::parse(&entry) {
+        Ok(entry) => entry,
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match unsuppress(&pool, &entry).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => HttpResponse::NotFound().finish(),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to unsuppress an entry");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
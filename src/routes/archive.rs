@@ -0,0 +1,71 @@
+use actix_web::{web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use crate::{
+    archive::ArchivePages,
+    configuration::ArchiveSettings,
+    newsletters::{get_archived_issue, list_archived_issues},
+};
+
+//NOTE: Published issues never change, a few minutes of caching only delays new ones
+const CACHE_CONTROL: (&str, &str) = ("Cache-Control", "public, max-age=300");
+
+pub async fn archive_index(
+    pool: web::Data<PgPool>,
+    pages: web::Data<ArchivePages>,
+) -> impl Responder {
+    let issues = match list_archived_issues(&pool, None).await {
+        Ok(issues) => issues,
+        Err(e) => return server_error(&e, "Failed to list the archived issues"),
+    };
+    match pages.index(&issues) {
+        Ok(page) => html(page),
+        Err(e) => server_error(&e, "Failed to render the archive"),
+    }
+}
+
+pub async fn archive_issue(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+    pages: web::Data<ArchivePages>,
+) -> impl Responder {
+    let issue = match get_archived_issue(&pool, &slug).await {
+        Ok(Some(issue)) => issue,
+        Ok(None) => return HttpResponse::NotFound().finish(),
+        Err(e) => return server_error(&e, "Failed to fetch an archived issue"),
+    };
+    match pages.issue(&issue) {
+        Ok(page) => html(page),
+        Err(e) => server_error(&e, "Failed to render an archived issue"),
+    }
+}
+
+pub async fn archive_feed(
+    pool: web::Data<PgPool>,
+    pages: web::Data<ArchivePages>,
+    settings: web::Data<ArchiveSettings>,
+) -> impl Responder {
+    let issues = match list_archived_issues(&pool, Some(settings.feed_entries.into())).await {
+        Ok(issues) => issues,
+        Err(e) => return server_error(&e, "Failed to list the archived issues"),
+    };
+    match pages.feed(&issues) {
+        Ok(feed) => HttpResponse::Ok()
+            .insert_header(CACHE_CONTROL)
+            .content_type("application/atom+xml; charset=utf-8")
+            .body(feed),
+        Err(e) => server_error(&e, "Failed to render the archive feed"),
+    }
+}
+
+fn html(page: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(CACHE_CONTROL)
+        .content_type("text/html; charset=utf-8")
+        .body(page)
+}
+
+fn server_error(e: &dyn std::error::Error, message: &str) -> HttpResponse {
+    tracing::error!(error.cause_chain = ?e, "{}", message);
+    HttpResponse::InternalServerError().finish()
+}
@@ -1,5 +1,13 @@
+mod admin;
+mod archive;
 mod health_check;
 mod subscriptions;
+mod tracking;
+mod webhooks;
 
+pub use admin::*;
+pub use archive::*;
 pub use health_check::*;
 pub use subscriptions::*;
+pub use tracking::*;
+pub use webhooks::*;
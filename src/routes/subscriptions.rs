@@ -1,67 +1,211 @@
 use actix_web::{web, HttpResponse, Responder};
 use chrono::Utc;
 use sqlx::PgPool;
-use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
-use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::{
+    anti_abuse::{AntiAbuse, Submission},
+    configuration::SubscriberNameSettings,
+    domain::{
+        EmailPolicy, NewSubscriber, SubscriberEmail, SubscriberEmailError, SubscriberNameError,
+        UnvalidatedSubscriberName,
+    },
+    lists::{join_list, leave_list_by_id, list_exists, DEFAULT_LIST_ID},
+    newsletters::UnsubscribeLinks,
+};
 
+//NOTE: The email is validated while deserializing, a malformed address is rejected by the `Form`
+//extractor itself. The name is checked in `parse`, against the rules registered as application data.
 #[derive(serde::Deserialize)]
 pub struct FormData {
-    email: String,
-    name: String,
+    email: SubscriberEmail,
+    name: UnvalidatedSubscriberName,
+    //NOTE: Hidden with CSS, only bots fill it in. Named like a field they would expect to find.
+    #[serde(default)]
+    website: Option<String>,
+    #[serde(default)]
+    form_token: Option<String>,
+    #[serde(default)]
+    captcha_response: Option<String>,
+    /// The id of the list to subscribe to, the default list when missing.
+    #[serde(default)]
+    list: Option<String>,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
+impl FormData {
+    fn submission(&self) -> Submission<'_> {
+        Submission {
+            honeypot: self.website.as_deref(),
+            form_token: self.form_token.as_deref(),
+            captcha_response: self.captcha_response.as_deref(),
+        }
+    }
+}
+
+impl FormData {
+    fn parse(
+        self,
+        name_rules: &SubscriberNameSettings,
+    ) -> Result<(NewSubscriber, String), ValidationError> {
+        let name = self.name.validate(name_rules)?;
+        let list_id = self.list.unwrap_or_else(|| DEFAULT_LIST_ID.to_string());
+        Ok((
+            NewSubscriber {
+                email: self.email,
+                name,
+            },
+            list_id,
+        ))
+    }
+}
+
+/// Body of the 400 returned when a field is rejected, e.g.
+/// `{"field": "name", "code": "too_long", "message": "..."}`.
+///
+/// `code` is stable, clients should use it to pick their own (translated) message.
+#[derive(Debug, serde::Serialize)]
+pub struct ValidationError {
+    field: &'static str,
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    suggestion: Option<String>,
+}
 
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let email = SubscriberEmail::parse(value.email)?;
-        let name = SubscriberName::parse(value.name)?;
-        Ok(NewSubscriber { email, name })
+impl From<SubscriberEmailError> for ValidationError {
+    fn from(e: SubscriberEmailError) -> Self {
+        let suggestion = match &e {
+            SubscriberEmailError::LikelyTypo { suggestion, .. } => Some(suggestion.clone()),
+            _ => None,
+        };
+        Self {
+            field: "email",
+            code: e.code(),
+            message: e.to_string(),
+            suggestion,
+        }
     }
-    // add code here
 }
 
+impl From<SubscriberNameError> for ValidationError {
+    fn from(e: SubscriberNameError) -> Self {
+        Self {
+            field: "name",
+            code: e.code(),
+            message: e.to_string(),
+            suggestion: None,
+        }
+    }
+}
+
+/// Answers `200 OK` for an address that bounced or complained as well: the form does not tell
+/// anyone what happened to an address, and `insert_subscriber` leaves it out of the sends.
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, app_state),
+    skip(form, app_state, anti_abuse, email_policy, name_rules),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
     )
 )]
-pub async fn subscribe(form: web::Form<FormData>, app_state: web::Data<PgPool>) -> impl Responder {
+pub async fn subscribe(
+    form: web::Form<FormData>,
+    app_state: web::Data<PgPool>,
+    anti_abuse: web::Data<AntiAbuse>,
+    email_policy: web::Data<EmailPolicy>,
+    name_rules: web::Data<SubscriberNameSettings>,
+) -> impl Responder {
+    //NOTE: Bots are not told which check they failed
+    if let Err(rejection) = anti_abuse.check(&form.submission()).await {
+        tracing::warn!(reason = %rejection, "Rejected a subscription as abusive");
+        return HttpResponse::BadRequest().finish();
+    }
+
     //NOTE: `web::Form` is a tuple struct around `FormData`
     // `form.0` gives us access to the underlying `FormData`
     // or we can use the `into_inner` method as well
 
-    let new_subscriber = match form.0.try_into() {
-        Ok(new_subscriber) => new_subscriber,
-        Err(error) => return HttpResponse::BadRequest().body(error),
+    let (new_subscriber, list_id) = match form.0.parse(&name_rules) {
+        Ok(parsed) => parsed,
+        Err(error) => return HttpResponse::BadRequest().json(error),
     };
+    match list_exists(app_state.get_ref(), &list_id).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::BadRequest().json(ValidationError {
+                field: "list",
+                code: "unknown_list",
+                message: format!("There is no list with id {}", list_id),
+                suggestion: None,
+            })
+        }
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to check that the list exists");
+            return HttpResponse::InternalServerError().finish();
+        }
+    }
+    if let Err(error) = email_policy.check(&new_subscriber.email).await {
+        return HttpResponse::BadRequest().json(ValidationError::from(error));
+    }
 
-    match insert_subscriber(app_state.get_ref(), &new_subscriber).await {
+    match insert_subscriber(app_state.get_ref(), &new_subscriber, &list_id).await {
         Ok(_) => HttpResponse::Ok().finish(),
         Err(_) => HttpResponse::InternalServerError().finish(),
     }
 }
 
-pub fn is_valid_name(s: &str) -> bool {
-    let is_empty_or_whitespace = s.trim().is_empty();
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeQuery {
+    token: String,
+}
 
-    //NOTE: A grapheme is defined by the Unicode standard as a "user-perceived" character: an
-    //example would be an Umlaut letter, but it is composed of two characters
-    //`graphemes` returns an iterator over the graphemes in the input `s`. `true` specifies that we
-    //want to use the extended grapheme definition set, the recommended one
-    let is_too_long = s.graphemes(true).count() > 256;
+/// Where the link at the bottom of every issue leads: the subscriber stops receiving the issues of
+/// that list, and stays subscribed to the other ones.
+///
+/// Following a link again, or the link of someone who has since been erased, is not an error.
+#[tracing::instrument(
+    name = "Unsubscribing from a list",
+    skip(query, pool, unsubscribe_links)
+)]
+pub async fn unsubscribe(
+    query: web::Query<UnsubscribeQuery>,
+    pool: web::Data<PgPool>,
+    unsubscribe_links: web::Data<UnsubscribeLinks>,
+) -> impl Responder {
+    let token = match unsubscribe_links.verify(&query.token) {
+        Some(token) => token,
+        None => return HttpResponse::BadRequest().body("Invalid unsubscribe link"),
+    };
+    match leave_list_by_id(&pool, token.subscriber_id, &token.list_id).await {
+        Ok(_) => HttpResponse::Ok()
+            .content_type("text/plain; charset=utf-8")
+            .body("You have been unsubscribed."),
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to unsubscribe");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
 
-    let forbidden_chars = ['/', '(', ')', '"', '<', '>', '\\', '{', '}'];
-    let contains_forbidden_chars = s.chars().any(|g| forbidden_chars.contains(&g));
+#[derive(serde::Serialize)]
+struct FormTokenResponse {
+    form_token: String,
+}
 
-    !(is_empty_or_whitespace || is_too_long || contains_forbidden_chars)
+/// Hands out the token to embed in the subscription form as `form_token`.
+pub async fn subscription_form_token(anti_abuse: web::Data<AntiAbuse>) -> impl Responder {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(FormTokenResponse {
+            form_token: anti_abuse.form_token(),
+        })
 }
 
+/// Subscribes to the list, adding the subscriber if the address is new: an address already known
+/// keeps its name and its other lists.
+///
+/// It also keeps its status: an address that bounced or complained joins the list but is still not
+/// sent anything, only the provider's webhook or an admin can bring it back.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(new_subscriber, pool)
@@ -69,23 +213,31 @@ pub fn is_valid_name(s: &str) -> bool {
 pub async fn insert_subscriber(
     pool: &PgPool,
     new_subscriber: &NewSubscriber,
+    list_id: &str,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query!(
-        r#"
-    INSERT INTO subscriptions (id, email,name, subscribed_at)
+    let result = async {
+        let mut transaction = pool.begin().await?;
+        //NOTE: The no-op update makes `RETURNING` give the id of an existing subscriber as well
+        let subscriber = sqlx::query!(
+            r#"
+    INSERT INTO subscriptions (id, email, name, subscribed_at)
     VALUES ($1, $2, $3, $4)
+    ON CONFLICT (email) DO UPDATE SET email = EXCLUDED.email
+    RETURNING id
     "#,
-        Uuid::new_v4(),
-        new_subscriber.email.as_ref(),
-        new_subscriber.name.as_ref(),
-        Utc::now()
-    )
-    .execute(pool)
-    .await
-    .map_err(|e| {
+            Uuid::new_v4(),
+            new_subscriber.email.as_ref(),
+            new_subscriber.name.as_ref(),
+            Utc::now()
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+        join_list(&mut transaction, subscriber.id, list_id).await?;
+        transaction.commit().await
+    }
+    .await;
+    result.map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
-    })?;
-
-    Ok(())
+    })
 }
@@ -0,0 +1,57 @@
+use actix_web::{http::header, web, HttpResponse, Responder};
+use sqlx::PgPool;
+
+use crate::newsletters::{record_tracking_event, Tracker, TrackingEvent, TrackingParams};
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// Records an open and serves the pixel. The pixel is served whatever happens: a broken image in
+/// the middle of an email helps no one.
+pub async fn track_open(
+    params: Option<web::Query<TrackingParams>>,
+    pool: web::Data<PgPool>,
+    tracker: web::Data<Tracker>,
+) -> impl Responder {
+    if let Some(params) = params {
+        if tracker.is_enabled() && tracker.verify(TrackingEvent::Open, &params) {
+            if let Err(e) = record_tracking_event(&pool, TrackingEvent::Open, &params).await {
+                tracing::error!(error.cause_chain = ?e, "Failed to record an open");
+            }
+        }
+    }
+    HttpResponse::Ok()
+        //NOTE: Every load should reach us, not a cached copy
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .content_type("image/gif")
+        .body(PIXEL)
+}
+
+/// Records a click and redirects to the original link, as long as the URL was signed by us.
+pub async fn track_click(
+    params: web::Query<TrackingParams>,
+    pool: web::Data<PgPool>,
+    tracker: web::Data<Tracker>,
+) -> impl Responder {
+    if !tracker.verify(TrackingEvent::Click, &params) {
+        return HttpResponse::BadRequest().body("Invalid tracking link");
+    }
+    let url = params
+        .url
+        .as_deref()
+        .expect("Verified click URLs have a destination");
+    //NOTE: Readers get to their destination even if the event cannot be recorded
+    if tracker.is_enabled() {
+        if let Err(e) = record_tracking_event(&pool, TrackingEvent::Click, &params).await {
+            tracing::error!(error.cause_chain = ?e, "Failed to record a click");
+        }
+    }
+    HttpResponse::Found()
+        .insert_header((header::LOCATION, url))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .finish()
+}
@@ -0,0 +1,86 @@
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use sqlx::PgPool;
+
+use super::admin::constant_time_eq;
+use crate::{
+    configuration::EmailEventsSettings,
+    email_events::{parse_event, process_event},
+};
+
+/// Receives the bounces and spam complaints reported by the email provider.
+///
+/// Answers `200 OK` once an event is applied, or if it was already applied (providers redeliver
+/// events whenever they are unsure we got them) or is of a kind we ignore. Errors on our side get
+/// a `500`, so that the provider tries again later.
+pub async fn email_events(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<EmailEventsSettings>,
+) -> impl Responder {
+    if let Err(reason) = authenticate(&request, &body, &settings) {
+        tracing::warn!(reason, "Rejected an email event webhook request");
+        return HttpResponse::Unauthorized()
+            .insert_header(("WWW-Authenticate", r#"Basic realm="email-events""#))
+            .finish();
+    }
+    let event = match parse_event(&body) {
+        Ok(Some(event)) => event,
+        Ok(None) => return HttpResponse::Ok().finish(),
+        Err(e) => return HttpResponse::BadRequest().body(e),
+    };
+    match process_event(&pool, &event).await {
+        Ok(true) => HttpResponse::Ok().finish(),
+        Ok(false) => {
+            tracing::info!(event_id = %event.id, "Ignored an email event received before");
+            HttpResponse::Ok().finish()
+        }
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, "Failed to process an email event");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn authenticate(
+    request: &HttpRequest,
+    body: &[u8],
+    settings: &EmailEventsSettings,
+) -> Result<(), &'static str> {
+    let credentials = request
+        .headers()
+        .get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok())
+        .ok_or("Missing basic auth credentials")?;
+    let (username, password) = credentials
+        .split_once(':')
+        .ok_or("Malformed basic auth credentials")?;
+    //NOTE: Both are compared, whatever the outcome of the first comparison
+    let valid_username = constant_time_eq(username, &settings.username);
+    let valid_password = constant_time_eq(password, settings.password.expose_secret());
+    if !(valid_username & valid_password) {
+        return Err("Invalid basic auth credentials");
+    }
+
+    if let Some(secret) = &settings.signing_secret {
+        let signature = request
+            .headers()
+            .get("X-Webhook-Signature")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| hex::decode(value.trim()).ok())
+            .ok_or("Missing signature")?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(body);
+        mac.verify_slice(&signature)
+            .map_err(|_| "Invalid signature")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{mpsc, watch},
+    time::Instant,
+};
+
+/// Resolves when the process is asked to stop, either by SIGTERM (e.g. from the orchestrator
+/// during a rolling deploy) or by SIGINT (Ctrl-C).
+pub async fn wait_for_signal() -> Result<(), std::io::Error> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+        _ = interrupt.recv() => tracing::info!("Received SIGINT"),
+    }
+    Ok(())
+}
+
+/// Coordinates the shutdown of background workers.
+///
+/// Every worker holds a [`ShutdownListener`]: it is told when to stop through
+/// [`ShutdownListener::recv`] and signals it is done by dropping the listener.
+pub struct Shutdown {
+    notify: watch::Sender<bool>,
+    //NOTE: Never used to send anything: `drain` waits for every clone held by the listeners to be
+    //dropped, at which point `recv` returns `None`.
+    done: (mpsc::Sender<()>, mpsc::Receiver<()>),
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (notify, _) = watch::channel(false);
+        Self {
+            notify,
+            done: mpsc::channel(1),
+        }
+    }
+
+    pub fn listener(&self) -> ShutdownListener {
+        ShutdownListener {
+            notify: self.notify.subscribe(),
+            _done: self.done.0.clone(),
+        }
+    }
+
+    /// Asks all workers to stop and waits until `deadline` for them to finish their current unit
+    /// of work. Returns `false` if some workers were still running when the deadline expired.
+    pub async fn drain(self, deadline: Instant) -> bool {
+        let Self {
+            notify,
+            done: (sender, mut receiver),
+        } = self;
+        let _ = notify.send(true);
+        drop(sender);
+        tokio::time::timeout_at(deadline, receiver.recv())
+            .await
+            .is_ok()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct ShutdownListener {
+    notify: watch::Receiver<bool>,
+    _done: mpsc::Sender<()>,
+}
+
+impl ShutdownListener {
+    pub fn is_shutdown(&self) -> bool {
+        *self.notify.borrow()
+    }
+
+    /// Resolves once shutdown has been requested. Meant to be used in a `tokio::select!` next to
+    /// the worker's own wait (e.g. polling the queue), never while an email is being sent.
+    pub async fn recv(&mut self) {
+        while !*self.notify.borrow_and_update() {
+            if self.notify.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::Shutdown;
+
+    #[tokio::test]
+    async fn drain_completes_once_every_listener_is_dropped() {
+        let shutdown = Shutdown::new();
+        let mut listener = shutdown.listener();
+        let worker = tokio::spawn(async move {
+            listener.recv().await;
+            assert!(listener.is_shutdown());
+        });
+
+        assert!(
+            shutdown
+                .drain(Instant::now() + Duration::from_secs(1))
+                .await
+        );
+        worker.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn drain_gives_up_after_the_deadline() {
+        let shutdown = Shutdown::new();
+        let _busy_worker = shutdown.listener();
+
+        assert!(
+            !shutdown
+                .drain(Instant::now() + Duration::from_millis(10))
+                .await
+        );
+    }
+}
@@ -1,30 +1,352 @@
-use std::net::TcpListener;
+use std::{io::Write, net::TcpListener, sync::Arc, time::Duration};
 
 use actix_web::{dev::Server, web, App, HttpServer};
 use sqlx::PgPool;
+use tokio::time::Instant;
 use tracing_actix_web::TracingLogger;
 
 use crate::{
+    anti_abuse::AntiAbuse,
+    archive::ArchivePages,
+    configuration::{DatabaseSettings, Settings},
+    domain::EmailPolicy,
     email_client::EmailClient,
-    routes::{health_check, subscribe},
+    email_templates::EmailTemplates,
+    migrations,
+    newsletters::{
+        run_scheduler_until_stopped, run_worker_until_stopped, Tracker, UnsubscribeLinks,
+    },
+    rate_limit::{RateLimit, RateLimiter},
+    reload::reload_on_sighup,
+    routes::{
+        add_list, add_suppression, archive_feed, archive_index, archive_issue,
+        create_newsletter_draft, edit_list, email_events, get_lists, get_log_filter,
+        get_newsletter, get_suppressions, health_check, list_newsletters, newsletter_report,
+        newsletter_stats, preview_newsletter, publish_draft, publish_newsletter,
+        remove_suppression, schedule_newsletter, send_test_newsletter, subscribe,
+        subscription_form_token, track_click, track_open, unschedule_newsletter, unsubscribe,
+        update_log_filter, update_newsletter, AdminToken,
+    },
+    shutdown::{wait_for_signal, Shutdown},
+    telemetry::LogFilterHandle,
 };
 
-pub fn run(
+/// A fully wired application, bound to its port but not yet serving requests.
+///
+/// Both the binary and the integration tests go through [`Application::build`], so that there is
+/// a single place deciding how the pieces fit together.
+pub struct Application {
+    port: u16,
+    server: Server,
+    pool: PgPool,
+    services: Services,
+    log_filter: LogFilterHandle,
+    configuration: Settings,
+    shutdown: Shutdown,
+}
+
+/// What the request handlers and the background workers share, built once by
+/// [`Application::build`].
+#[derive(Clone)]
+struct Services {
+    email_client: Arc<EmailClient>,
+    email_templates: Arc<EmailTemplates>,
+    rate_limiter: Arc<RateLimiter>,
+    tracker: Arc<Tracker>,
+    unsubscribe_links: Arc<UnsubscribeLinks>,
+}
+
+impl Application {
+    pub async fn build(
+        configuration: Settings,
+        log_filter: LogFilterHandle,
+    ) -> Result<Self, std::io::Error> {
+        if let Some(directives) = &configuration.application.log_filter {
+            log_filter
+                .set(directives)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        }
+
+        let pool = get_connection_pool(&configuration.database);
+        if configuration.database.migrate_on_startup {
+            migrations::run(&pool)
+                .await
+                .map_err(std::io::Error::other)?;
+        }
+
+        let email_client = configuration
+            .email_client
+            .client()
+            .map(|client| Arc::new(client.with_suppression_list(pool.clone())))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let email_templates = EmailTemplates::load(&configuration.email_templates.directory)
+            .map(Arc::new)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let services = Services {
+            email_client,
+            email_templates,
+            rate_limiter: Arc::new(RateLimiter::new(configuration.rate_limit.clone(), &pool)),
+            tracker: Arc::new(Tracker::new(
+                &configuration.tracking,
+                &configuration.application.base_url,
+            )),
+            unsubscribe_links: Arc::new(UnsubscribeLinks::new(
+                &configuration.unsubscribe,
+                &configuration.application.base_url,
+            )),
+        };
+
+        //NOTE: Binding to port 0 lets the OS pick a random free port, we read it back from the
+        //listener so that callers (e.g. the test suite) know where to send requests.
+        let address = format!(
+            "{}:{}",
+            configuration.application.host, configuration.application.port
+        );
+        let listener = TcpListener::bind(address)?;
+        let port = listener.local_addr()?.port();
+        let server = run(
+            listener,
+            pool.clone(),
+            services.clone(),
+            log_filter.clone(),
+            &configuration,
+        )?;
+
+        Ok(Self {
+            port,
+            server,
+            pool,
+            services,
+            log_filter,
+            configuration,
+            shutdown: Shutdown::new(),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Serves requests until SIGTERM/SIGINT, then shuts down gracefully: in-flight requests and
+    /// background workers are given `application.shutdown_timeout_seconds`, altogether, to finish
+    /// before the pool is closed.
+    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
+        let Self {
+            server,
+            pool,
+            services,
+            log_filter,
+            configuration,
+            shutdown,
+            ..
+        } = self;
+        let shutdown_timeout =
+            Duration::from_secs(configuration.application.shutdown_timeout_seconds);
+
+        tokio::spawn(run_worker_until_stopped(
+            pool.clone(),
+            services.email_client.clone(),
+            services.email_templates,
+            services.tracker,
+            services.unsubscribe_links,
+            configuration.delivery.clone(),
+            shutdown.listener(),
+        ));
+        tokio::spawn(run_scheduler_until_stopped(
+            pool.clone(),
+            configuration.delivery.clone(),
+            shutdown.listener(),
+        ));
+
+        tokio::spawn(reload_on_sighup(
+            configuration,
+            services.email_client,
+            services.rate_limiter,
+            log_filter,
+        ));
+
+        let server_handle = server.handle();
+        let mut server = tokio::spawn(server);
+        tokio::select! {
+            result = wait_for_signal() => result?,
+            //NOTE: The server only stops on its own if something went wrong
+            result = &mut server => return result?,
+        }
+
+        tracing::info!("Shutting down, no longer accepting connections");
+        //NOTE: The server and the workers stop side by side, against the same deadline. A graceful
+        //stop waits for in-flight requests up to `shutdown_timeout` (see `run`), starting now.
+        let deadline = Instant::now() + shutdown_timeout;
+        let (stopped, drained) = tokio::join!(
+            async {
+                server_handle.stop(true).await;
+                server.await
+            },
+            shutdown.drain(deadline),
+        );
+        stopped??;
+        if !drained {
+            tracing::warn!("Background workers did not stop within the shutdown timeout");
+        }
+        pool.close().await;
+        tracing::info!("Shutdown complete");
+        std::io::stdout().flush()
+    }
+}
+
+//NOTE: The pool is lazy: connections are only established when first needed, so the application
+//can boot even if Postgres is temporarily unreachable.
+pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
+    configuration
+        .pool_options()
+        .connect_lazy_with(configuration.with_db())
+}
+
+fn run(
     listener: TcpListener,
     pool: PgPool,
-    email_client: EmailClient,
+    services: Services,
+    log_filter: LogFilterHandle,
+    configuration: &Settings,
 ) -> Result<Server, std::io::Error> {
-    let email_client = web::Data::new(email_client);
+    let email_client = web::Data::from(services.email_client);
+    let email_templates = web::Data::from(services.email_templates);
+    let tracker = web::Data::from(services.tracker);
+    let unsubscribe_links = web::Data::from(services.unsubscribe_links);
+    let rate_limiter = web::Data::from(services.rate_limiter);
+    let log_filter = web::Data::new(log_filter);
+    let admin_token = web::Data::new(AdminToken(configuration.application.admin_token.clone()));
+    let anti_abuse = web::Data::new(AntiAbuse::new(&configuration.anti_abuse));
+    let email_policy = web::Data::new(EmailPolicy::new(&configuration.email_policy)?);
+    let name_rules = web::Data::new(configuration.subscriber_name.clone());
+    let archive = if configuration.archive.enabled {
+        let pages = ArchivePages::load(&configuration.archive, &configuration.application.base_url)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        Some((
+            web::Data::new(pages),
+            web::Data::new(configuration.archive.clone()),
+        ))
+    } else {
+        None
+    };
+    let email_events_settings = configuration
+        .email_events
+        .enabled
+        .then(|| web::Data::new(configuration.email_events.clone()));
+    let shutdown_timeout = Duration::from_secs(configuration.application.shutdown_timeout_seconds);
     let server = HttpServer::new(move || {
         App::new()
             .route("/health_check", web::get().to(health_check))
-            .route("/subscriptions", web::post().to(subscribe))
+            .service(
+                web::resource("/subscriptions")
+                    .wrap(RateLimit)
+                    .route(web::post().to(subscribe)),
+            )
+            .route(
+                "/subscriptions/form_token",
+                web::get().to(subscription_form_token),
+            )
+            .route("/subscriptions/unsubscribe", web::get().to(unsubscribe))
+            .service(
+                web::resource("/admin/log_filter")
+                    .route(web::get().to(get_log_filter))
+                    .route(web::put().to(update_log_filter)),
+            )
+            .service(
+                web::resource("/admin/lists")
+                    .route(web::get().to(get_lists))
+                    .route(web::post().to(add_list)),
+            )
+            .route("/admin/lists/{id}", web::put().to(edit_list))
+            .service(
+                web::resource("/admin/newsletters")
+                    .route(web::get().to(list_newsletters))
+                    .route(web::post().to(publish_newsletter)),
+            )
+            //NOTE: Registered before `/admin/newsletters/{id}`, which would match it as well
+            .route(
+                "/admin/newsletters/drafts",
+                web::post().to(create_newsletter_draft),
+            )
+            .service(
+                web::resource("/admin/newsletters/{id}")
+                    .route(web::get().to(get_newsletter))
+                    .route(web::put().to(update_newsletter)),
+            )
+            .route(
+                "/admin/newsletters/{id}/preview",
+                web::get().to(preview_newsletter),
+            )
+            .route(
+                "/admin/newsletters/{id}/test",
+                web::post().to(send_test_newsletter),
+            )
+            .service(
+                web::resource("/admin/newsletters/{id}/schedule")
+                    .route(web::put().to(schedule_newsletter))
+                    .route(web::delete().to(unschedule_newsletter)),
+            )
+            .route(
+                "/admin/newsletters/{id}/publish",
+                web::post().to(publish_draft),
+            )
+            .route(
+                "/admin/newsletters/{id}/stats",
+                web::get().to(newsletter_stats),
+            )
+            .route(
+                "/admin/newsletters/{id}/report",
+                web::get().to(newsletter_report),
+            )
+            .service(
+                web::resource("/admin/suppressions")
+                    .route(web::get().to(get_suppressions))
+                    .route(web::post().to(add_suppression)),
+            )
+            .route(
+                "/admin/suppressions/{entry}",
+                web::delete().to(remove_suppression),
+            )
+            .route("/tracking/open", web::get().to(track_open))
+            .route("/tracking/click", web::get().to(track_click))
+            //NOTE: A disabled archive is not routed at all, `/archive` answers 404
+            .configure(|config| {
+                if let Some((pages, settings)) = &archive {
+                    config
+                        .app_data(pages.clone())
+                        .app_data(settings.clone())
+                        .route("/archive", web::get().to(archive_index))
+                        //NOTE: Registered before `/archive/{slug}`, which would match it as well
+                        .route("/archive/feed.xml", web::get().to(archive_feed))
+                        .route("/archive/{slug}", web::get().to(archive_issue));
+                }
+            })
+            .configure(|config| {
+                if let Some(settings) = &email_events_settings {
+                    config
+                        .app_data(settings.clone())
+                        .route("/webhooks/email-events", web::post().to(email_events));
+                }
+            })
             //NOTE: Register the connection pool as part of the application state
             .app_data(web::Data::new(pool.clone()))
             .app_data(email_client.clone())
+            .app_data(rate_limiter.clone())
+            .app_data(log_filter.clone())
+            .app_data(admin_token.clone())
+            .app_data(anti_abuse.clone())
+            .app_data(email_policy.clone())
+            .app_data(name_rules.clone())
+            .app_data(email_templates.clone())
+            .app_data(tracker.clone())
+            .app_data(unsubscribe_links.clone())
             .wrap(TracingLogger::default())
     })
     .listen(listener)?
+    //NOTE: Signals are handled by `run_until_stopped`, which has to stop background workers and
+    //close the pool after the server is done with in-flight requests.
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout.as_secs())
     .run();
     Ok(server)
 }
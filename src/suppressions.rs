@@ -0,0 +1,219 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use sqlx::{postgres::PgExecutor, PgPool};
+
+use crate::domain::SubscriberEmail;
+
+/// An entry of the suppression list: a single address, or every address of a domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SuppressionEntry {
+    Address(String),
+    Domain(String),
+}
+
+impl SuppressionEntry {
+    /// `user@example.com` is an address; `example.com` and `@example.com` are domains.
+    pub fn parse(entry: &str) -> Result<Self, String> {
+        let entry = entry.trim();
+        match entry.strip_prefix('@') {
+            Some(domain) => Self::domain(domain),
+            None if entry.contains('@') => SubscriberEmail::parse(entry.to_string())
+                .map(|email| Self::address(&email))
+                .map_err(|e| e.to_string()),
+            None => Self::domain(entry),
+        }
+    }
+
+    pub fn address(email: &SubscriberEmail) -> Self {
+        Self::Address(email.as_ref().to_lowercase())
+    }
+
+    fn domain(domain: &str) -> Result<Self, String> {
+        match idna::domain_to_ascii(domain) {
+            Ok(domain) if domain.contains('.') => Ok(Self::Domain(domain)),
+            _ => Err(format!("{} is not a valid domain", domain)),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Address(_) => "address",
+            Self::Domain(_) => "domain",
+        }
+    }
+
+    fn value(&self) -> &str {
+        match self {
+            Self::Address(value) | Self::Domain(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for SuppressionEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{}", address),
+            Self::Domain(domain) => write!(f, "@{}", domain),
+        }
+    }
+}
+
+/// Why an entry is on the list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SuppressionReason {
+    HardBounce,
+    SpamComplaint,
+    /// The subscriber asked for their data to be erased, see `subscribers erase`.
+    Erasure,
+    /// Added by an admin.
+    Manual,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HardBounce => "hard_bounce",
+            Self::SpamComplaint => "spam_complaint",
+            Self::Erasure => "erasure",
+            Self::Manual => "manual",
+        }
+    }
+}
+
+impl TryFrom<String> for SuppressionReason {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "hard_bounce" => Ok(Self::HardBounce),
+            "spam_complaint" => Ok(Self::SpamComplaint),
+            "erasure" => Ok(Self::Erasure),
+            "manual" => Ok(Self::Manual),
+            other => Err(format!("{} is not a valid suppression reason", other)),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Suppression {
+    /// `user@example.com` or `@example.com`.
+    pub entry: String,
+    pub reason: SuppressionReason,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Adds `entry` to the list. Returns `false` if it already was, in which case its original reason
+/// is kept.
+pub async fn suppress<'e>(
+    executor: impl PgExecutor<'e>,
+    entry: &SuppressionEntry,
+    reason: SuppressionReason,
+    note: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+    INSERT INTO suppressions (kind, value, reason, note, created_at)
+    VALUES ($1, $2, $3, $4, $5)
+    ON CONFLICT (kind, value) DO NOTHING
+    "#,
+        entry.kind(),
+        entry.value(),
+        reason.as_str(),
+        note,
+        Utc::now()
+    )
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns `false` if `entry` was not on the list.
+pub async fn unsuppress(pool: &PgPool, entry: &SuppressionEntry) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM suppressions WHERE kind = $1 AND value = $2",
+        entry.kind(),
+        entry.value()
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether `email`, or its domain, is on the list.
+pub async fn is_suppressed(pool: &PgPool, email: &SubscriberEmail) -> Result<bool, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+    SELECT EXISTS (
+        SELECT 1 FROM suppressions
+        WHERE (kind = 'address' AND value = $1) OR (kind = 'domain' AND value = $2)
+    ) AS "suppressed!"
+    "#,
+        email.as_ref().to_lowercase(),
+        email.domain()
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(row.suppressed)
+}
+
+/// The whole list, most recent first.
+pub async fn list_suppressions(pool: &PgPool) -> Result<Vec<Suppression>, sqlx::Error> {
+    sqlx::query!(
+        r#"
+    SELECT kind, value, reason, note, created_at
+    FROM suppressions
+    ORDER BY created_at DESC
+    "#
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| {
+        let entry = match row.kind.as_str() {
+            "domain" => SuppressionEntry::Domain(row.value),
+            _ => SuppressionEntry::Address(row.value),
+        };
+        Ok(Suppression {
+            entry: entry.to_string(),
+            reason: row
+                .reason
+                .try_into()
+                .map_err(|e: String| sqlx::Error::Decode(e.into()))?,
+            note: row.note,
+            created_at: row.created_at,
+        })
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SuppressionEntry;
+
+    #[test]
+    fn entries_are_addresses_or_domains() {
+        assert_eq!(
+            SuppressionEntry::parse("Ursula@Example.COM"),
+            Ok(SuppressionEntry::Address("ursula@example.com".into()))
+        );
+        assert_eq!(
+            SuppressionEntry::parse("@Example.com"),
+            Ok(SuppressionEntry::Domain("example.com".into()))
+        );
+        assert_eq!(
+            SuppressionEntry::parse("bücher.example"),
+            Ok(SuppressionEntry::Domain("xn--bcher-kva.example".into()))
+        );
+        assert!(SuppressionEntry::parse("localhost").is_err());
+        assert!(SuppressionEntry::parse("not an@address").is_err());
+    }
+
+    #[test]
+    fn domains_are_displayed_with_a_leading_at() {
+        let entry = SuppressionEntry::parse("example.com").unwrap();
+        assert_eq!(entry.to_string(), "@example.com");
+    }
+}
@@ -1,24 +1,42 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
 use tracing::{subscriber::set_global_default, Subscriber};
 use tracing_bunyan_formatter::BunyanFormattingLayer;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, EnvFilter, Registry};
 
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+) -> (impl Subscriber + Send + Sync, LogFilterHandle)
 where
     //NOTE: This syntax is
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    //NOTE: Wrapping the filter in a `reload::Layer` lets us swap it while the process is running,
+    //the handle is the only way to reach it once the subscriber has been installed.
+    let (env_filter, handle) = reload::Layer::new(env_filter);
     //NOTE: This is layer that is going to output the resulting tracing event records
     let formatting_layer = BunyanFormattingLayer::new(name, sink);
-    Registry::default()
+    let subscriber = Registry::default()
         .with(env_filter)
         .with(tracing_bunyan_formatter::JsonStorageLayer) //I assume this will create a `default instance`
-        .with(formatting_layer)
+        .with(formatting_layer);
+    (
+        subscriber,
+        LogFilterHandle {
+            handle,
+            generation: Arc::new(AtomicU64::new(0)),
+        },
+    )
 }
 
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
@@ -26,3 +44,88 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     //It should only be called once!
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
+
+/// Handle to the [`EnvFilter`] of the subscriber returned by [`get_subscriber`].
+#[derive(Clone, Debug)]
+pub struct LogFilterHandle {
+    handle: reload::Handle<EnvFilter, Registry>,
+    //NOTE: Bumped on every change, so that a pending revert can tell whether somebody else
+    //changed the filter in the meantime.
+    generation: Arc<AtomicU64>,
+}
+
+impl LogFilterHandle {
+    /// Returns the directives currently in use, e.g. `info,sqlx=warn`.
+    pub fn current(&self) -> Result<String, String> {
+        self.handle
+            .with_current(|filter| filter.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replaces the active filter. The previous filter stays in place if `directives` is invalid.
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        self.replace(directives).map(|_| ())
+    }
+
+    /// Replaces the active filter for `ttl`, after which the previous filter is restored.
+    ///
+    /// The revert is skipped if the filter is changed again before `ttl` elapses.
+    pub fn set_temporarily(&self, directives: &str, ttl: Duration) -> Result<(), String> {
+        let previous = self.current()?;
+        let generation = self.replace(directives)?;
+        let handle = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ttl).await;
+            if handle.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            match handle.set(&previous) {
+                Ok(()) => tracing::info!(log_filter = %previous, "Reverted the log filter"),
+                Err(e) => tracing::error!(error.message = %e, "Failed to revert the log filter"),
+            }
+        });
+        Ok(())
+    }
+
+    fn replace(&self, directives: &str) -> Result<u64, String> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| format!("`{}` is not a valid log filter: {}", directives, e))?;
+        self.handle.reload(filter).map_err(|e| e.to_string())?;
+        Ok(self.generation.fetch_add(1, Ordering::SeqCst) + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::get_subscriber;
+
+    //NOTE: The subscribers are not installed: each test changes its own filter only
+    #[tokio::test]
+    async fn a_temporary_filter_is_reverted_once_it_expires() {
+        let (_subscriber, log_filter) = get_subscriber("test".into(), "info".into(), std::io::sink);
+        let original = log_filter.current().unwrap();
+
+        log_filter
+            .set_temporarily("zero2prod=trace", Duration::from_millis(20))
+            .unwrap();
+        assert_eq!(log_filter.current().unwrap(), "zero2prod=trace");
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(log_filter.current().unwrap(), original);
+    }
+
+    #[tokio::test]
+    async fn a_filter_set_in_the_meantime_is_not_reverted() {
+        let (_subscriber, log_filter) = get_subscriber("test".into(), "info".into(), std::io::sink);
+
+        log_filter
+            .set_temporarily("zero2prod=trace", Duration::from_millis(20))
+            .unwrap();
+        log_filter.set("warn").unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(log_filter.current().unwrap(), "warn");
+    }
+}
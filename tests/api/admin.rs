@@ -0,0 +1,91 @@
+use zero2prod::migrations::{self, MigrationState};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn admin_endpoints_reject_requests_without_a_valid_token() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let test_cases = vec![
+        (None, "no token"),
+        (Some("Bearer wrong-token"), "a wrong token"),
+        (Some("Basic YWRtaW46YWRtaW4="), "basic auth"),
+    ];
+
+    for (authorization, description) in test_cases {
+        let mut request = client.get(format!("{}/admin/log_filter", test_app.address));
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let response = request.send().await.expect("Failed to execute request. ");
+
+        assert_eq!(
+            401,
+            response.status().as_u16(),
+            "The API did not return 401 Unauthorized when the request had {description}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn log_filter_can_be_changed_by_an_admin() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+    let url = format!("{}/admin/log_filter", test_app.address);
+
+    let response = client
+        .put(&url)
+        .bearer_auth(&test_app.admin_token)
+        .json(&serde_json::json!({ "log_filter": "zero2prod=trace", "ttl_seconds": 60 }))
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+    assert_eq!(200, response.status().as_u16());
+
+    let response = client
+        .get(&url)
+        .bearer_auth(&test_app.admin_token)
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["log_filter"], "zero2prod=trace");
+}
+
+#[tokio::test]
+async fn invalid_log_filter_is_rejected() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let response = client
+        .put(format!("{}/admin/log_filter", test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&serde_json::json!({ "log_filter": "info,[" }))
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn migrations_can_be_reverted_and_reapplied() {
+    let test_app = spawn_app().await;
+
+    let status = migrations::status(&test_app.db_pool).await.unwrap();
+    assert!(status.iter().all(|m| m.state == MigrationState::Applied));
+    let latest = status.last().unwrap().version;
+
+    let reverted = migrations::revert(&test_app.db_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(reverted.version, latest);
+    let status = migrations::status(&test_app.db_pool).await.unwrap();
+    assert_eq!(status.last().unwrap().state, MigrationState::Pending);
+
+    migrations::run(&test_app.db_pool).await.unwrap();
+    let status = migrations::status(&test_app.db_pool).await.unwrap();
+    assert!(status.iter().all(|m| m.state == MigrationState::Applied));
+}
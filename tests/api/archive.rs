@@ -0,0 +1,54 @@
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn published_newsletters_are_listed_in_the_public_archive() {
+    let test_app = spawn_app().await;
+    let draft = test_app
+        .create_newsletter_draft("Not yet", "Still a draft")
+        .await;
+    for title in ["Issue #6: Hello!", "Issue #6: Hello!"] {
+        let response = test_app
+            .admin_newsletters(
+                reqwest::Method::POST,
+                "",
+                Some(serde_json::json!({ "title": title, "markdown": "Some **news**" })),
+            )
+            .await;
+        assert_eq!(202, response.status().as_u16());
+    }
+    let client = reqwest::Client::new();
+    let get = |path: &str| client.get(format!("{}{}", test_app.address, path)).send();
+
+    let index = get("/archive").await.unwrap().text().await.unwrap();
+    let issue = get("/archive/issue-6-hello-2").await.unwrap();
+    let feed = get("/archive/feed.xml").await.unwrap();
+    let unpublished = get(&format!("/archive/{}", draft)).await.unwrap();
+
+    //NOTE: Slugs of issues sharing a title get a suffix. `/` is escaped in attributes.
+    assert!(index.contains(r#"issue-6-hello">"#));
+    assert!(index.contains(r#"issue-6-hello-2">"#));
+    assert!(!index.contains("Not yet"));
+    assert_eq!(200, issue.status().as_u16());
+    assert!(issue
+        .text()
+        .await
+        .unwrap()
+        .contains("<strong>news</strong>"));
+    assert_eq!(
+        feed.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    assert_eq!(feed.text().await.unwrap().matches("<entry>").count(), 2);
+    assert_eq!(404, unpublished.status().as_u16());
+}
+
+#[tokio::test]
+async fn the_archive_can_be_disabled() {
+    let test_app = spawn_app_with(|c| c.archive.enabled = false).await;
+
+    let response = reqwest::get(format!("{}/archive", test_app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(404, response.status().as_u16());
+}
@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+use wiremock::{matchers::path, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn subscribers_can_be_managed_from_the_cli() {
+    let test_app = spawn_app().await;
+
+    let output = test_app.cli(&[
+        "subscribers",
+        "add",
+        "--email",
+        "ursula_le_guin@gmail.com",
+        "--name",
+        "le guin",
+    ]);
+    assert!(output.status.success());
+
+    let output = test_app.cli(&["subscribers", "list"]);
+    let listed = String::from_utf8(output.stdout).unwrap();
+    assert!(listed.contains("ursula_le_guin@gmail.com\tle guin"));
+
+    let output = test_app.cli(&["subscribers", "remove", "ursula_le_guin@gmail.com"]);
+    assert!(output.status.success());
+    let remaining = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(remaining.is_empty());
+}
+
+#[tokio::test]
+async fn cli_rejects_invalid_subscribers() {
+    let test_app = spawn_app().await;
+
+    let output = test_app.cli(&[
+        "subscribers",
+        "add",
+        "--email",
+        "not-an-email",
+        "--name",
+        "x",
+    ]);
+
+    assert!(!output.status.success());
+    let disposable = test_app.cli(&[
+        "subscribers",
+        "add",
+        "--email",
+        "ursula@mailinator.com",
+        "--name",
+        "le guin",
+    ]);
+    assert!(!disposable.status.success());
+    assert!(String::from_utf8(disposable.stderr)
+        .unwrap()
+        .contains("mailinator.com"));
+}
+
+#[tokio::test]
+async fn failing_emails_can_be_inspected_and_retried_from_the_cli() {
+    let test_app = spawn_app_with(|c| c.delivery.retry_delay_seconds = 3600).await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&test_app.email_server)
+        .await;
+    let id = test_app.create_newsletter_draft("Issue #15", "Hello").await;
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", id), None)
+        .await;
+    //NOTE: The retry is recorded once the provider answered, wait for it rather than the request
+    for _ in 0..100 {
+        let retried = sqlx::query!("SELECT n_retries FROM issue_delivery_queue")
+            .fetch_one(&test_app.db_pool)
+            .await
+            .unwrap();
+        if retried.n_retries == 1 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    let output = test_app.cli(&["queue", "status"]);
+    let status = String::from_utf8(output.stdout).unwrap();
+    assert!(status.starts_with(&format!("{}\tIssue #15\t1\t1\t", id)));
+
+    let output = test_app.cli(&["queue", "retry", "--issue", &id]);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Rescheduled 1 emails\n"
+    );
+    //NOTE: Due right away rather than in an hour
+    test_app.wait_for_email_requests(2).await;
+}
@@ -0,0 +1,18 @@
+use crate::helpers::spawn_app;
+
+//NOTE: `tokio::test` is the testing equivalent of `tokio::main`.
+//You can inspect the generated code using `cargo expand --test api health_check`
+#[tokio::test]
+async fn health_check_works() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let response = client
+        .get(format!("{}/health_check", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert!(response.status().is_success());
+    assert_eq!(Some(0), response.content_length())
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use secrecy::ExposeSecret;
+use sqlx::{Connection, Executor, PgConnection, PgPool};
+use uuid::Uuid;
+use wiremock::MockServer;
+use zero2prod::{
+    configuration::{get_configuration, DatabaseSettings, Settings},
+    startup::{get_connection_pool, Application},
+    telemetry::{get_subscriber, init_subscriber},
+};
+
+static TRACING: Lazy<()> = Lazy::new(|| {
+    let default_level = "info";
+    let subscriber_name = "test";
+
+    //WARN: We cannot assign the output of `get_subscriber` to a variable based on value of `TEST_LOG`
+    //to avoid repetitions because the sink is part of the actual concrete type returned by
+    //`get_subscriber` (something like `Layered<...,Sink,>`), therefore they are not the same type.
+    if std::env::var("TEST_LOG").is_ok() {
+        let (subscriber, _) = get_subscriber(
+            subscriber_name.into(),
+            default_level.into(),
+            std::io::stdout,
+        );
+        init_subscriber(subscriber);
+    } else {
+        let (subscriber, _) =
+            get_subscriber(subscriber_name.into(), default_level.into(), std::io::sink);
+        init_subscriber(subscriber);
+    }
+});
+
+pub struct TestApp {
+    pub address: String,
+    pub db_pool: PgPool,
+    pub db_name: String,
+    pub admin_token: String,
+    pub email_server: MockServer,
+    //NOTE: Owns the filter the app's `/admin/log_filter` changes. The filter of the global
+    //subscriber is shared by every test running concurrently, it is left alone.
+    _log_filter_subscriber: Box<dyn tracing::Subscriber + Send + Sync>,
+}
+
+impl TestApp {
+    /// Runs the `zero2prod` binary against this app's database.
+    pub fn cli(&self, args: &[&str]) -> std::process::Output {
+        std::process::Command::new(env!("CARGO_BIN_EXE_zero2prod"))
+            .args(args)
+            .env("APP_DATABASE__DB_NAME", &self.db_name)
+            .output()
+            .expect("Failed to run the CLI")
+    }
+
+    pub async fn post_subscription(&self, body: &'static str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions", self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to execute request. ")
+    }
+
+    /// Sends an authenticated request to `/admin/newsletters{path}`.
+    pub async fn admin_newsletters(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        body: Option<serde_json::Value>,
+    ) -> reqwest::Response {
+        let mut request = reqwest::Client::new()
+            .request(
+                method,
+                format!("{}/admin/newsletters{}", self.address, path),
+            )
+            .bearer_auth(&self.admin_token);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        request.send().await.expect("Failed to execute request. ")
+    }
+
+    /// Creates a draft and returns its id.
+    pub async fn create_newsletter_draft(&self, title: &str, markdown: &str) -> String {
+        let response = self
+            .admin_newsletters(
+                reqwest::Method::POST,
+                "/drafts",
+                Some(serde_json::json!({ "title": title, "markdown": markdown })),
+            )
+            .await;
+        assert_eq!(201, response.status().as_u16());
+        let body: serde_json::Value = response.json().await.unwrap();
+        body["newsletter_issue_id"].as_str().unwrap().to_string()
+    }
+
+    pub async fn queued_emails(&self) -> i64 {
+        sqlx::query!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .count
+    }
+
+    /// Waits for the email provider to have received `n` requests, successful or not.
+    pub async fn wait_for_email_requests(&self, n: usize) {
+        for _ in 0..100 {
+            if self.email_server.received_requests().await.unwrap().len() >= n {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("The email provider did not receive {} requests in time", n);
+    }
+
+    /// Waits for the background worker to send every queued newsletter email.
+    pub async fn wait_for_delivery_queue_to_drain(&self) {
+        for _ in 0..100 {
+            if self.queued_emails().await == 0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("The delivery queue was not drained in time");
+    }
+}
+
+//NOTE: `tokio::test` is the testing equivalent of `tokio::main`.
+//You can inspect the generated code using `cargo expand --test health_check (<- name of the file)`
+
+//NOTE: This function is the only piece in our tests that depends on the application code.
+//Everything else is decoupled from the underlying implementation details
+pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like [`spawn_app`], letting the test tweak the configuration before the application is built.
+pub async fn spawn_app_with(customize: impl FnOnce(&mut Settings)) -> TestApp {
+    //The first time `initialize` is invoked the code in `TRACING` is executed.
+    //All other invocations will instead skip execution.
+    Lazy::force(&TRACING);
+
+    //NOTE: Stands in for the email provider's API
+    let email_server = MockServer::start().await;
+
+    let configuration = {
+        let mut c = get_configuration().expect("Failed to read configuration");
+        //NOTE: Every test gets its own database and a random OS-assigned port
+        c.database.db_name = Uuid::new_v4().to_string();
+        c.application.host = "127.0.0.1".into();
+        c.application.port = 0;
+        c.email_client.base_url = email_server.uri();
+        c.delivery.poll_interval_milliseconds = 10;
+        c.delivery.scheduler_interval_milliseconds = 10;
+        customize(&mut c);
+        c
+    };
+
+    configure_database(&configuration.database).await;
+
+    let (log_filter_subscriber, log_filter) =
+        get_subscriber("test".into(), "info".into(), std::io::sink);
+    let application = Application::build(configuration.clone(), log_filter)
+        .await
+        .expect("Failed to build application");
+    let address = format!("http://127.0.0.1:{}", application.port());
+    //NOTE: We need to use `tokio::spawn` to run it as a background task
+    tokio::spawn(application.run_until_stopped());
+
+    TestApp {
+        address,
+        db_pool: get_connection_pool(&configuration.database),
+        db_name: configuration.database.db_name.clone(),
+        admin_token: configuration
+            .application
+            .admin_token
+            .expose_secret()
+            .clone(),
+        email_server,
+        _log_filter_subscriber: Box::new(log_filter_subscriber),
+    }
+}
+
+//NOTE: Migrations are run by `Application::build`, here we only create the temporary database
+async fn configure_database(config: &DatabaseSettings) {
+    let mut connection = PgConnection::connect_with(&config.without_db())
+        .await
+        .expect("Failed to connect to Postgres");
+
+    connection
+        .execute(format!(r#"CREATE DATABASE "{}";"#, config.db_name).as_str())
+        .await
+        .expect("Failed to create DB");
+}
@@ -0,0 +1,161 @@
+use wiremock::{matchers::path, Mock, ResponseTemplate};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn issues_go_to_the_subscribers_of_their_list_from_the_list_sender() {
+    let test_app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    let created = reqwest::Client::new()
+        .post(format!("{}/admin/lists", test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&serde_json::json!({
+            "list_id": "weekly-digest",
+            "name": "Weekly digest",
+            "sender_email": "digest@zero2prod.com",
+            "sender_name": "The Digest",
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(201, created.status().as_u16());
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com")
+        .await;
+    let joined = test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com&list=weekly-digest")
+        .await;
+    let unknown = test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com&list=monthly")
+        .await;
+    assert_eq!(200, joined.status().as_u16());
+    assert_eq!(400, unknown.status().as_u16());
+    let error: serde_json::Value = unknown.json().await.unwrap();
+    assert_eq!(error["code"], "unknown_list");
+
+    for (title, list) in [("Digest #1", "weekly-digest"), ("Issue #14", "default")] {
+        let response = test_app
+            .admin_newsletters(
+                reqwest::Method::POST,
+                "",
+                Some(serde_json::json!({ "title": title, "markdown": "Hello", "list": list })),
+            )
+            .await;
+        assert_eq!(202, response.status().as_u16());
+        test_app.wait_for_delivery_queue_to_drain().await;
+    }
+
+    let mut sent: Vec<_> = test_app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|request| {
+            let email: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            format!("{} -> {}: {}", email["from"], email["to"], email["subject"])
+        })
+        .collect();
+    sent.sort();
+    assert_eq!(
+        sent,
+        [
+            r#""\"The Digest\" <digest@zero2prod.com>" -> "octavia_butler@gmail.com": "Digest #1""#,
+            r#""test@gmail.com" -> "octavia_butler@gmail.com": "Issue #14""#,
+            r#""test@gmail.com" -> "ursula_le_guin@gmail.com": "Issue #14""#,
+        ]
+    );
+
+    let output = test_app.cli(&[
+        "subscribers",
+        "remove",
+        "octavia_butler@gmail.com",
+        "--list",
+        "weekly-digest",
+    ]);
+    assert!(output.status.success());
+    let output = test_app.cli(&["subscribers", "list"]);
+    let listed = String::from_utf8(output.stdout).unwrap();
+    assert!(listed.contains("octavia_butler@gmail.com\toctavia\tactive\tdefault\n"));
+    let next = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "",
+            Some(serde_json::json!({ "title": "Digest #2", "markdown": "Hi", "list": "weekly-digest" })),
+        )
+        .await;
+    assert_eq!(202, next.status().as_u16());
+    assert_eq!(test_app.queued_emails().await, 0);
+    let unknown = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "/drafts",
+            Some(serde_json::json!({ "title": "Lost", "markdown": "Hi", "list": "monthly" })),
+        )
+        .await;
+    assert_eq!(400, unknown.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribers_leave_the_list_of_an_issue_through_its_unsubscribe_link() {
+    let test_app = spawn_app().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.email_server)
+        .await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    let published = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "",
+            Some(serde_json::json!({ "title": "Issue #1", "markdown": "Hello" })),
+        )
+        .await;
+    assert_eq!(202, published.status().as_u16());
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let text = email["text_body"].as_str().unwrap();
+    let link = text
+        .lines()
+        .find_map(|line| line.strip_prefix("Unsubscribe: "))
+        .expect("Every issue has an unsubscribe link");
+    //NOTE: Links are built from `application.base_url`, not from the random port of the test
+    let (_, path_and_query) = link.split_once("/subscriptions/unsubscribe").unwrap();
+    let url = format!(
+        "{}/subscriptions/unsubscribe{}",
+        test_app.address, path_and_query
+    );
+    let client = reqwest::Client::new();
+
+    let tampered = client.get(format!("{}0", url)).send().await.unwrap();
+    assert_eq!(400, tampered.status().as_u16());
+    for _ in 0..2 {
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    let subscription = sqlx::query!("SELECT status FROM list_subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, "unsubscribed");
+    let next = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "",
+            Some(serde_json::json!({ "title": "Issue #2", "markdown": "Hello again" })),
+        )
+        .await;
+    assert_eq!(202, next.status().as_u16());
+    assert_eq!(test_app.queued_emails().await, 0);
+}
@@ -0,0 +1,11 @@
+mod admin;
+mod archive;
+mod cli;
+mod health_check;
+mod helpers;
+mod lists;
+mod newsletters;
+mod subscriptions;
+mod suppressions;
+mod tracking;
+mod webhooks;
@@ -0,0 +1,363 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::newsletters::publish_due_issue;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn newsletters_written_in_markdown_are_delivered_to_every_subscriber() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let markdown =
+        "# Issue #1\n\nSome `code`:\n\n```rust\nfn main() {}\n```\n\n<script>alert(1)</script>";
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/newsletters", test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .json(&serde_json::json!({ "title": "Issue #1", "markdown": markdown }))
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+    assert_eq!(202, response.status().as_u16());
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let html = email["html_body"].as_str().unwrap();
+    assert_eq!(email["subject"], "Issue #1");
+    assert!(html.contains("<h1>Issue #1</h1>"));
+    assert!(html.contains("<pre style="));
+    assert!(html.contains("Hi le guin,"));
+    assert!(!html.contains("<script>"));
+    assert!(email["text_body"].as_str().unwrap().contains("Issue #1"));
+
+    let issue = sqlx::query!("SELECT markdown_content FROM newsletter_issues")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.markdown_content.as_deref(), Some(markdown));
+}
+
+#[tokio::test]
+async fn newsletters_can_be_published_from_the_cli() {
+    let test_app = spawn_app().await;
+    let markdown = std::env::temp_dir().join(format!("{}.md", Uuid::new_v4()));
+    std::fs::write(&markdown, "Hello *world*").unwrap();
+
+    let output = test_app.cli(&[
+        "newsletters",
+        "publish",
+        "--title",
+        "Issue #2",
+        "--markdown",
+        markdown.to_str().unwrap(),
+    ]);
+
+    assert!(output.status.success());
+    let issue = sqlx::query!("SELECT title, html_content, markdown_content FROM newsletter_issues")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.title, "Issue #2");
+    assert!(issue.html_content.contains("<em>world</em>"));
+    assert_eq!(issue.markdown_content.as_deref(), Some("Hello *world*"));
+}
+
+#[tokio::test]
+async fn newsletter_drafts_can_be_edited_previewed_and_test_sent() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    let id = test_app
+        .create_newsletter_draft("Draft", "First *take*")
+        .await;
+    let response = test_app
+        .admin_newsletters(
+            reqwest::Method::PUT,
+            &format!("/{}", id),
+            Some(serde_json::json!({ "title": "Issue #3", "markdown": "Second *take*" })),
+        )
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    let issue: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(issue["status"], "draft");
+    assert_eq!(issue["markdown_content"], "Second *take*");
+
+    let preview = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/preview", id), None)
+        .await;
+    assert_eq!(
+        preview.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    assert!(preview.text().await.unwrap().contains("<em>take</em>"));
+
+    let response = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            &format!("/{}/test", id),
+            Some(serde_json::json!({ "email": "editor@example.com" })),
+        )
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    assert_eq!(email["to"], "editor@example.com");
+    assert_eq!(email["subject"], "[Test] Issue #3");
+    //NOTE: Drafts are never queued for the subscribers
+    assert_eq!(test_app.queued_emails().await, 0);
+}
+
+#[tokio::test]
+async fn scheduled_newsletters_are_published_once_due() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    let id = test_app.create_newsletter_draft("Issue #4", "Later").await;
+    let schedule_path = format!("/{}/schedule", id);
+    let schedule = |send_at: chrono::DateTime<chrono::Utc>| {
+        test_app.admin_newsletters(
+            reqwest::Method::PUT,
+            &schedule_path,
+            Some(serde_json::json!({ "send_at": send_at })),
+        )
+    };
+
+    let in_the_past = schedule(chrono::Utc::now() - chrono::Duration::minutes(1)).await;
+    let in_a_second = schedule(chrono::Utc::now() + chrono::Duration::seconds(1)).await;
+
+    assert_eq!(400, in_the_past.status().as_u16());
+    assert_eq!(200, in_a_second.status().as_u16());
+    let status = || async {
+        let issue: serde_json::Value = test_app
+            .admin_newsletters(reqwest::Method::GET, &format!("/{}", id), None)
+            .await
+            .json()
+            .await
+            .unwrap();
+        issue["status"].as_str().unwrap().to_string()
+    };
+    assert_eq!(status().await, "scheduled");
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(status().await, "published");
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let edit = test_app
+        .admin_newsletters(
+            reqwest::Method::PUT,
+            &format!("/{}", id),
+            Some(serde_json::json!({ "title": "Too late", "markdown": "Too late" })),
+        )
+        .await;
+    assert_eq!(409, edit.status().as_u16());
+}
+
+#[tokio::test]
+async fn delivery_reports_log_every_recipient_with_the_provider_message_id() {
+    let test_app = spawn_app_with(|c| c.delivery.max_attempts = 1).await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(body_partial_json(
+            serde_json::json!({ "to": "octavia_butler@gmail.com" }),
+        ))
+        .respond_with(ResponseTemplate::new(422))
+        .mount(&test_app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "MessageID": "message-1", "ErrorCode": 0 })),
+        )
+        .mount(&test_app.email_server)
+        .await;
+    let id = test_app.create_newsletter_draft("Issue #9", "Hello").await;
+
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", id), None)
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+    sqlx::query!("DELETE FROM subscriptions WHERE email = 'ursula_le_guin@gmail.com'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let report: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/report", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["counts"]["pending"], 0);
+    assert_eq!(report["counts"]["delivered"], 1);
+    assert_eq!(report["counts"]["failed"], 1);
+    assert_eq!(report["counts"]["unsubscribed"], 1);
+    assert_eq!(report["page"], 1);
+    assert_eq!(report["next_page"], serde_json::Value::Null);
+    let recipients = report["recipients"].as_array().unwrap();
+    let delivered = recipients
+        .iter()
+        .find(|r| r["status"] == "delivered")
+        .unwrap();
+    assert_eq!(delivered["subscriber_email"], "ursula_le_guin@gmail.com");
+    assert_eq!(delivered["provider_message_id"], "message-1");
+    let failed = recipients.iter().find(|r| r["status"] == "failed").unwrap();
+    assert!(failed["error"].as_str().unwrap().contains("422"));
+
+    let page = test_app
+        .admin_newsletters(
+            reqwest::Method::GET,
+            &format!("/{}/report?format=html", id),
+            None,
+        )
+        .await;
+    assert_eq!(page.headers()["Content-Type"], "text/html; charset=utf-8");
+    assert!(page.text().await.unwrap().contains("message-1"));
+
+    //NOTE: Past the last page the log is empty, the counts still cover every recipient
+    let report: serde_json::Value = test_app
+        .admin_newsletters(
+            reqwest::Method::GET,
+            &format!("/{}/report?page=2", id),
+            None,
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["recipients"], serde_json::json!([]));
+    assert_eq!(report["counts"]["delivered"], 1);
+    let invalid_page = test_app
+        .admin_newsletters(
+            reqwest::Method::GET,
+            &format!("/{}/report?page=0", id),
+            None,
+        )
+        .await;
+    assert_eq!(invalid_page.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn issues_the_templates_fail_on_are_reported_as_failed_deliveries() {
+    //NOTE: Renders the startup sample fine, fails on the issue below
+    let directory = std::env::temp_dir().join(format!("templates-{}", Uuid::new_v4()));
+    copy_directory(std::path::Path::new("templates/email"), &directory);
+    std::fs::write(
+        directory.join("newsletter.txt"),
+        "{% if title == \"Broken\" %}{{ missing }}{% endif %}\n{% include \"partials/footer.txt\" %}",
+    )
+    .unwrap();
+    let test_app = spawn_app_with(|c| c.email_templates.directory = directory).await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.email_server)
+        .await;
+    let id = test_app.create_newsletter_draft("Broken", "Hello").await;
+
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", id), None)
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let report: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/report", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["counts"]["failed"], 1);
+    let failed = &report["recipients"][0];
+    assert_eq!(failed["subscriber_email"], "ursula_le_guin@gmail.com");
+    assert_eq!(failed["status"], "failed");
+    assert!(failed["error"].as_str().unwrap().contains("render"));
+}
+
+fn copy_directory(from: &std::path::Path, to: &std::path::Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let path = entry.unwrap().path();
+        let target = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy_directory(&path, &target);
+        } else {
+            std::fs::copy(&path, target).unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn concurrent_schedulers_publish_each_due_issue_once() {
+    //NOTE: The application's own scheduler is kept out of the way
+    let test_app = spawn_app_with(|c| c.delivery.scheduler_interval_milliseconds = 3_600_000).await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com")
+        .await;
+    let id = test_app.create_newsletter_draft("Issue #5", "Once").await;
+    sqlx::query!(
+        "UPDATE newsletter_issues SET status = 'scheduled', scheduled_for = now() WHERE newsletter_issue_id = $1",
+        Uuid::parse_str(&id).unwrap()
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    let schedulers: Vec<_> = (0..8)
+        .map(|_| {
+            let pool = test_app.db_pool.clone();
+            tokio::spawn(async move { publish_due_issue(&pool).await.unwrap() })
+        })
+        .collect();
+    let mut published = 0;
+    for scheduler in schedulers {
+        published += scheduler.await.unwrap().is_some() as usize;
+    }
+
+    assert_eq!(published, 1);
+    //NOTE: No mock is mounted, the provider answers 404 and every email stays queued for a retry
+    assert_eq!(test_app.queued_emails().await, 2);
+}
@@ -0,0 +1,299 @@
+use std::time::Duration;
+
+use zero2prod::{
+    configuration::{get_configuration, RateLimitBackend},
+    rate_limit::{Decision, RateLimiter},
+};
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn subscribe_returns_200_for_valid_form_data() {
+    let test_app = spawn_app().await;
+
+    let client = reqwest::Client::new();
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    let response = client
+        .post(format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert_eq!(200, response.status().as_u16());
+
+    let pool = test_app.db_pool;
+    let saved = sqlx::query!("SELECT email, name FROM subscriptions")
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_data_is_missing() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let test_cases = vec![
+        ("name=le%20guin", "missing the email"),
+        ("name=&email=ursula_le_guin%40gmail.com", "name is empty"),
+        ("email=ursula_le_guin%40gmail.com", "missing the name"),
+        ("", "missing both name and email"),
+    ];
+
+    for (invalid_body, error_message) in test_cases {
+        let response = client
+            .post(format!("{}/subscriptions", test_app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(invalid_body)
+            .send()
+            .await
+            .expect("Failed to execute request. ");
+
+        assert_eq!(400,response.status().as_u16(),
+        "The API did not fail but returned 400 Bad Request when the payload was {error_message}");
+    }
+}
+
+#[tokio::test]
+async fn subscribe_returns_429_when_the_same_email_is_submitted_too_often() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let mut last_response = None;
+    //NOTE: Duplicated submissions are accepted, but still count towards the limit
+    for _ in 0..4 {
+        let response = client
+            .post(format!("{}/subscriptions", test_app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("name=le%20guin&email=Ursula_le_guin%40gmail.com")
+            .send()
+            .await
+            .expect("Failed to execute request. ");
+        last_response = Some(response);
+    }
+
+    let response = last_response.unwrap();
+    assert_eq!(429, response.status().as_u16());
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn subscribe_returns_429_when_an_ip_sends_too_many_requests() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let mut statuses = Vec::new();
+    for i in 0..11 {
+        let response = client
+            .post(format!("{}/subscriptions", test_app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!("name=le%20guin&email=ursula_{}%40gmail.com", i))
+            .send()
+            .await
+            .expect("Failed to execute request. ");
+        statuses.push(response.status().as_u16());
+    }
+
+    assert_eq!(statuses[..10], [200; 10]);
+    assert_eq!(statuses[10], 429);
+}
+
+#[tokio::test]
+async fn postgres_rate_limit_buckets_are_shared_between_limiters() {
+    let test_app = spawn_app().await;
+    let mut settings = get_configuration().unwrap().rate_limit;
+    settings.backend = RateLimitBackend::Postgres;
+    let bucket = settings.per_email;
+    //NOTE: Two limiters on the same database behave like two instances of the application
+    let first = RateLimiter::new(settings.clone(), &test_app.db_pool);
+    let second = RateLimiter::new(settings, &test_app.db_pool);
+
+    for i in 0..bucket.capacity {
+        let limiter = if i % 2 == 0 { &first } else { &second };
+        assert_eq!(
+            limiter.acquire("email:a@b.com", &bucket).await.unwrap(),
+            Decision::Allowed
+        );
+    }
+
+    assert!(matches!(
+        first.acquire("email:a@b.com", &bucket).await.unwrap(),
+        Decision::Limited { .. }
+    ));
+}
+
+#[tokio::test]
+async fn postgres_rate_limit_buckets_are_pruned_once_full() {
+    let test_app = spawn_app().await;
+    let mut settings = get_configuration().unwrap().rate_limit;
+    settings.backend = RateLimitBackend::Postgres;
+    let bucket = settings.per_email;
+    sqlx::query!(
+        r#"
+    INSERT INTO rate_limit_buckets (key, tokens, updated_at, full_at)
+    VALUES ('email:old@b.com', 0, now() - interval '1 day', now() - interval '1 hour')
+    "#
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    let limiter = RateLimiter::new(settings, &test_app.db_pool);
+
+    limiter.acquire("email:a@b.com", &bucket).await.unwrap();
+
+    let keys: Vec<String> = sqlx::query!("SELECT key FROM rate_limit_buckets")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.key)
+        .collect();
+    assert_eq!(keys, ["email:a@b.com"]);
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_honeypot_is_filled_in() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let response = client
+        .post(format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com&website=http%3A%2F%2Fspam.com")
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert_eq!(400, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_for_disposable_email_addresses() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let response = client
+        .post(format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=le%20guin&email=ursula%40Mailinator.com")
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert_eq!(400, response.status().as_u16());
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["field"], "email");
+    assert_eq!(error["code"], "disposable_domain");
+}
+
+#[tokio::test]
+async fn subscribe_returns_a_structured_error_for_invalid_names() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app().await;
+
+    let response = client
+        .post(format!("{}/subscriptions", test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=%7Bursula%7D&email=ursula_le_guin%40gmail.com")
+        .send()
+        .await
+        .expect("Failed to execute request. ");
+
+    assert_eq!(400, response.status().as_u16());
+    let error: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(error["field"], "name");
+    assert_eq!(error["code"], "forbidden_char");
+    assert!(error["message"].as_str().unwrap().contains('{'));
+}
+
+#[tokio::test]
+async fn each_application_validates_names_against_its_own_rules() {
+    let client = reqwest::Client::new();
+    //NOTE: Both run in this process, the rules of one must not leak into the other
+    let strict = spawn_app_with(|c| c.subscriber_name.max_graphemes = 4).await;
+    let lenient = spawn_app().await;
+
+    for (app, expected) in [(&strict, 400), (&lenient, 200)] {
+        let response = client
+            .post(format!("{}/subscriptions", app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("name=ursula&email=ursula_le_guin%40gmail.com")
+            .send()
+            .await
+            .expect("Failed to execute request. ");
+        assert_eq!(expected, response.status().as_u16());
+    }
+}
+
+#[tokio::test]
+async fn subscribe_requires_a_form_token_old_enough_when_enabled() {
+    let client = reqwest::Client::new();
+    let test_app = spawn_app_with(|c| {
+        c.anti_abuse.form_token.enabled = true;
+        c.anti_abuse.form_token.min_fill_seconds = 1;
+    })
+    .await;
+    let subscribe = |body: String| {
+        client
+            .post(format!("{}/subscriptions", test_app.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+    };
+    let form_token: serde_json::Value = client
+        .get(format!("{}/subscriptions/form_token", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request. ")
+        .json()
+        .await
+        .unwrap();
+    let form_token = form_token["form_token"].as_str().unwrap().to_string();
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&form_token={}",
+        form_token
+    );
+
+    let missing = subscribe("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .unwrap();
+    let too_fast = subscribe(body.clone()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+    let accepted = subscribe(body).await.unwrap();
+
+    assert_eq!(400, missing.status().as_u16());
+    assert_eq!(400, too_fast.status().as_u16());
+    assert_eq!(200, accepted.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribing_again_does_not_reactivate_a_bounced_address() {
+    let test_app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    test_app.post_subscription(body).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = test_app.post_subscription(body).await;
+
+    //NOTE: The form does not tell that the address bounced
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.status, "bounced");
+}
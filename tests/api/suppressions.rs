@@ -0,0 +1,106 @@
+use wiremock::{
+    matchers::{body_partial_json, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn suppressed_addresses_and_domains_are_never_mailed() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    test_app
+        .post_subscription("name=octavia&email=octavia_butler%40outlook.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(body_partial_json(
+            serde_json::json!({ "to": "ursula_le_guin@gmail.com" }),
+        ))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    let suppress = |entry: &'static str| {
+        reqwest::Client::new()
+            .post(format!("{}/admin/suppressions", test_app.address))
+            .bearer_auth(&test_app.admin_token)
+            .json(&serde_json::json!({ "entry": entry, "note": "Asked by phone" }))
+            .send()
+    };
+
+    let added = suppress("Outlook.com").await.unwrap();
+    let again = suppress("@outlook.com").await.unwrap();
+    let invalid = suppress("not a domain").await.unwrap();
+
+    assert_eq!(201, added.status().as_u16());
+    assert_eq!(200, again.status().as_u16());
+    assert_eq!(400, invalid.status().as_u16());
+    let id = test_app.create_newsletter_draft("Issue #12", "Hello").await;
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", id), None)
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+    let report: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/report", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["counts"]["delivered"], 1);
+    assert_eq!(report["counts"]["suppressed"], 1);
+
+    let output = test_app.cli(&["subscribers", "erase", "ursula_le_guin@gmail.com"]);
+    assert!(output.status.success());
+    let deliveries = sqlx::query!("SELECT subscriber_email FROM issue_deliveries")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].subscriber_email, "octavia_butler@outlook.com");
+    let listed: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}/admin/suppressions", test_app.address))
+        .bearer_auth(&test_app.admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(listed[0]["entry"], "ursula_le_guin@gmail.com");
+    assert_eq!(listed[0]["reason"], "erasure");
+    assert_eq!(listed[1]["entry"], "@outlook.com");
+    assert_eq!(listed[1]["reason"], "manual");
+    assert_eq!(listed[1]["note"], "Asked by phone");
+
+    //NOTE: Re-subscribing does not lift the suppression
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    let next = test_app.create_newsletter_draft("Issue #13", "Hello").await;
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", next), None)
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+    let removed = reqwest::Client::new()
+        .delete(format!(
+            "{}/admin/suppressions/@outlook.com",
+            test_app.address
+        ))
+        .bearer_auth(&test_app.admin_token)
+        .send()
+        .await
+        .unwrap();
+    let missing = reqwest::Client::new()
+        .delete(format!(
+            "{}/admin/suppressions/outlook.com",
+            test_app.address
+        ))
+        .bearer_auth(&test_app.admin_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(200, removed.status().as_u16());
+    assert_eq!(404, missing.status().as_u16());
+}
@@ -0,0 +1,112 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn opens_and_clicks_on_tracked_newsletters_are_counted() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+    let response = test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "",
+            Some(serde_json::json!({
+                "title": "Issue #7",
+                "markdown": "Read [the post](https://example.com/post?a=1&b=2).",
+                "tracking": true,
+            })),
+        )
+        .await;
+    assert_eq!(202, response.status().as_u16());
+    let id = response.json::<serde_json::Value>().await.unwrap()["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let html = email["html_body"].as_str().unwrap();
+    //NOTE: Tracking URLs point at `application.base_url`, not at the port the test app bound
+    let tracking_path = |prefix: &str| {
+        let start = html.find(prefix).expect("No tracking URL in the email");
+        let end = start + html[start..].find('"').unwrap();
+        html[start..end].replace("&amp;", "&")
+    };
+    let open = tracking_path("/tracking/open?");
+    let click = tracking_path("/tracking/click?");
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let get = |path: String| client.get(format!("{}{}", test_app.address, path)).send();
+
+    let pixel = get(open.clone()).await.unwrap();
+    get(open).await.unwrap();
+    let redirect = get(click.clone()).await.unwrap();
+    let forged = get(click.replace("example.com", "evil.example.com"))
+        .await
+        .unwrap();
+
+    assert_eq!(pixel.headers()["Content-Type"], "image/gif");
+    assert_eq!(302, redirect.status().as_u16());
+    assert_eq!(
+        redirect.headers()["Location"],
+        "https://example.com/post?a=1&b=2"
+    );
+    assert_eq!(400, forged.status().as_u16());
+    let stats: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/stats", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["opens"], 2);
+    assert_eq!(stats["unique_opens"], 1);
+    assert_eq!(stats["clicks"], 1);
+    assert_eq!(stats["links"][0]["url"], "https://example.com/post?a=1&b=2");
+}
+
+#[tokio::test]
+async fn tracking_can_be_disabled_for_every_issue() {
+    let test_app = spawn_app_with(|c| c.tracking.enabled = false).await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.email_server)
+        .await;
+
+    test_app
+        .admin_newsletters(
+            reqwest::Method::POST,
+            "",
+            Some(serde_json::json!({
+                "title": "Issue #8",
+                "markdown": "Read [the post](https://example.com/post).",
+                "tracking": true,
+            })),
+        )
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+
+    let requests = test_app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+    let html = email["html_body"].as_str().unwrap();
+    assert!(!html.contains("/tracking/"));
+    assert!(html.contains(r#"href="https://example.com/post""#));
+}
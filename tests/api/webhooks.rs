@@ -0,0 +1,105 @@
+use wiremock::{
+    matchers::{body_partial_json, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn hard_bounces_and_complaints_reported_by_the_provider_stop_future_sends() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    test_app
+        .post_subscription("name=octavia&email=octavia_butler%40gmail.com")
+        .await;
+    for (recipient, message_id) in [
+        ("ursula_le_guin@gmail.com", "message-ursula"),
+        ("octavia_butler@gmail.com", "message-octavia"),
+    ] {
+        Mock::given(path("/email"))
+            .and(body_partial_json(serde_json::json!({ "to": recipient })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "MessageID": message_id })),
+            )
+            .mount(&test_app.email_server)
+            .await;
+    }
+    let id = test_app.create_newsletter_draft("Issue #10", "Hello").await;
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", id), None)
+        .await;
+    test_app.wait_for_delivery_queue_to_drain().await;
+    let post_event = |event: serde_json::Value, password: &'static str| {
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email-events", test_app.address))
+            .basic_auth("postmark", Some(password))
+            .json(&event)
+            .send()
+    };
+    let bounce = serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": 42,
+        "Type": "HardBounce",
+        "MessageID": "message-ursula",
+        "Email": "ursula_le_guin@gmail.com",
+        "BouncedAt": "2026-10-19T16:33:54.9070259Z",
+    });
+    let complaint = serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "ID": 43,
+        "Type": "SpamComplaint",
+        "MessageID": "message-octavia",
+        "Email": "octavia_butler@gmail.com",
+        "BouncedAt": "2026-10-19T16:35:00Z",
+    });
+
+    let forged = post_event(bounce.clone(), "guessed").await.unwrap();
+    let first = post_event(bounce.clone(), "my_email_events_password")
+        .await
+        .unwrap();
+    let redelivered = post_event(bounce, "my_email_events_password")
+        .await
+        .unwrap();
+    let complained = post_event(complaint, "my_email_events_password")
+        .await
+        .unwrap();
+
+    assert_eq!(401, forged.status().as_u16());
+    assert_eq!(200, first.status().as_u16());
+    assert_eq!(200, redelivered.status().as_u16());
+    assert_eq!(200, complained.status().as_u16());
+    let statuses = sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(statuses[0].status, "complained");
+    assert_eq!(statuses[1].status, "bounced");
+    let events = sqlx::query!(r#"SELECT count(*) AS "count!" FROM email_events"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.count, 2);
+    let suppressions = sqlx::query!("SELECT value, reason FROM suppressions ORDER BY value")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppressions[0].reason, "spam_complaint");
+    assert_eq!(suppressions[1].reason, "hard_bounce");
+    let report: serde_json::Value = test_app
+        .admin_newsletters(reqwest::Method::GET, &format!("/{}/report", id), None)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["counts"]["bounced"], 1);
+
+    let next = test_app.create_newsletter_draft("Issue #11", "Hello").await;
+    test_app
+        .admin_newsletters(reqwest::Method::POST, &format!("/{}/publish", next), None)
+        .await;
+    //NOTE: Neither of them is queued
+    assert_eq!(test_app.queued_emails().await, 0);
+}